@@ -0,0 +1,71 @@
+#!/usr/bin/env python3
+"""Measures the peak memory and time of importing a generated dict.cc file.
+
+Every given binary imports the same file into an empty data directory, once with
+the entries counted beforehand and once with --no-precount, e.g.
+
+    cargo build --release
+    benches/import_peak_rss.py target/release/dictcc-cli --lines 1000000
+"""
+
+import argparse
+import os
+import subprocess
+import tempfile
+import time
+
+
+def write_fixture(path, lines):
+    with open(path, "w", encoding="utf-8") as file:
+        file.write("# DE-EN vocabulary database\n")
+        for number in range(lines):
+            file.write(
+                f"Wort{number} <Wörter{number}> {{n}}\tword{number} [the {number}th one]\tnoun\t[ling.]\n"
+            )
+
+
+def import_peak_rss(binary, fixture, args):
+    """The peak resident memory in MiB and the seconds of one import, None if it failed"""
+    with tempfile.TemporaryDirectory() as data_home:
+        env = dict(os.environ, XDG_DATA_HOME=data_home)
+        start = time.monotonic()
+        process = subprocess.Popen(
+            [binary, "import", *args, fixture],
+            env=env,
+            stdout=subprocess.DEVNULL,
+            stderr=subprocess.DEVNULL,
+        )
+        _, status, usage = os.wait4(process.pid, 0)
+        seconds = time.monotonic() - start
+        if os.waitstatus_to_exitcode(status) != 0:
+            return None
+    # Kilobytes on Linux, bytes on macOS
+    divisor = 1024 * 1024 if os.uname().sysname == "Darwin" else 1024
+    return usage.ru_maxrss / divisor, seconds
+
+
+def main():
+    parser = argparse.ArgumentParser(description=__doc__.splitlines()[0])
+    parser.add_argument("binaries", nargs="+", help="dictcc-cli builds to compare")
+    parser.add_argument("--lines", type=int, default=400_000, help="entries of the generated file")
+    options = parser.parse_args()
+
+    with tempfile.TemporaryDirectory() as fixture_dir:
+        fixture = os.path.join(fixture_dir, "de-en.txt")
+        write_fixture(fixture, options.lines)
+        size = os.path.getsize(fixture) / (1024 * 1024)
+        print(f"{options.lines} entries, {size:.0f} MiB")
+        for binary in options.binaries:
+            for args in ([], ["--no-precount"]):
+                label = " ".join([binary, *args])
+                result = import_peak_rss(binary, fixture, args)
+                if result is None:
+                    # e.g. builds from before --no-precount
+                    print(f"{label:60} failed")
+                else:
+                    peak_rss, seconds = result
+                    print(f"{label:60} {peak_rss:7.1f} MiB {seconds:7.1f} s")
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,90 @@
+use std::ffi::OsString;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use clap::Command;
+
+use crate::database;
+use crate::error::DictCliError;
+
+/// A name for the arguments it stands for, e.g. `g = --language-pair de-en --from en`
+struct Alias {
+    name: String,
+    args: Vec<String>,
+}
+
+/// Aliases one per line, the arguments are separated by whitespace and lines starting with `#` are comments
+fn aliases_path() -> Result<PathBuf, DictCliError> {
+    Ok(database::data_dir()?.join("aliases"))
+}
+
+fn aliases() -> Result<Vec<Alias>, DictCliError> {
+    let path = aliases_path()?;
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err.into()),
+    };
+
+    let mut aliases = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (name, args) = match line.split_once('=') {
+            Some((name, args)) if is_alias_name(name.trim()) => (name.trim(), args),
+            _ => {
+                return Err(DictCliError::InvalidAlias(
+                    path.to_string_lossy().into_owned(),
+                    index + 1,
+                ))
+            }
+        };
+        aliases.push(Alias {
+            name: name.to_owned(),
+            args: args.split_whitespace().map(str::to_owned).collect(),
+        });
+    }
+    Ok(aliases)
+}
+
+fn is_alias_name(name: &str) -> bool {
+    !name.is_empty() && !name.starts_with('-') && !name.contains(char::is_whitespace)
+}
+
+/// Replaces the first argument by the arguments of its alias, e.g. `dictcc-cli g hello` becomes
+/// `dictcc-cli --language-pair de-en --from en hello`. An alias must not have the name of a subcommand.
+pub(crate) fn expand_aliases(args: Vec<OsString>, command: &Command) -> Result<Vec<OsString>, DictCliError> {
+    let aliases = aliases()?;
+    if aliases.is_empty() {
+        return Ok(args);
+    }
+
+    if let Some(alias) = aliases.iter().find(|alias| {
+        alias.name == "help"
+            || command
+                .get_subcommands()
+                .any(|subcommand| subcommand.get_name() == alias.name)
+    }) {
+        return Err(DictCliError::AliasCollision(
+            alias.name.clone(),
+            aliases_path()?.to_string_lossy().into_owned(),
+        ));
+    }
+
+    let alias = args
+        .get(1)
+        .and_then(|arg| arg.to_str())
+        .and_then(|arg| aliases.iter().find(|alias| alias.name == arg));
+    Ok(match alias {
+        Some(alias) => {
+            let mut expanded = Vec::with_capacity(args.len() + alias.args.len());
+            expanded.push(args[0].clone());
+            expanded.extend(alias.args.iter().map(OsString::from));
+            expanded.extend(args.into_iter().skip(2));
+            expanded
+        }
+        None => args,
+    })
+}
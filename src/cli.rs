@@ -1,38 +1,214 @@
+use std::ffi::OsStr;
 use std::path::PathBuf;
 
-use clap::builder::{NonEmptyStringValueParser, PathBufValueParser, PossibleValuesParser};
-use clap::{arg, crate_description, crate_name, crate_version, ArgMatches, Command};
+use clap::builder::{
+    NonEmptyStringValueParser, PathBufValueParser, PossibleValue, PossibleValuesParser, TypedValueParser,
+};
+use clap::{arg, crate_description, crate_name, crate_version, Arg, ArgAction, ArgMatches, Command};
+use comfy_table::presets::{
+    ASCII_BORDERS_ONLY, ASCII_FULL, ASCII_HORIZONTAL_ONLY, ASCII_MARKDOWN, ASCII_NO_BORDERS, NOTHING,
+    UTF8_BORDERS_ONLY, UTF8_FULL, UTF8_HORIZONTAL_ONLY, UTF8_NO_BORDERS,
+};
+use comfy_table::CellAlignment;
+use itertools::Itertools;
 
-use crate::database;
-use crate::error::DictCliError;
+use crate::database::{FuzzyMode, ImportFormat, ImportOptions, LabelField, TokenizerKind};
+use crate::error::{self, DictCliError};
+use crate::state::{self, LastSelection};
+use crate::style::{self, ColorChoice};
+use crate::template::Template;
+use crate::{alias, database, language};
+
+#[derive(Clone, Copy)]
+pub(crate) enum Rank {
+    Similarity,
+    Frequency,
+    /// Grouped by the stored word classes, then by similarity
+    WordClasses,
+}
+
+/// A column of the result table, see `--column-order`
+#[derive(Clone, Copy)]
+pub(crate) enum TableColumn {
+    Source,
+    Target,
+    WordClasses,
+    SubjectLabels,
+    /// The annotations in angle brackets of the searched entry
+    Extra,
+    Phonetic,
+    SourceFile,
+    /// Added after the other columns with `--back-translate`
+    BackTranslation,
+}
+
+#[derive(Clone)]
+pub(crate) enum OutputFormat {
+    Table,
+    Json,
+    JsonLines,
+    Template(Template),
+    /// Only the target of the best result as plain text
+    First,
+    /// Tab separated front and back of a flashcard with HTML escaped content, for the note importer of Anki
+    Anki,
+}
 
 pub(crate) enum Settings {
     Import {
         file: PathBuf,
-        force: bool,
+        options: ImportOptions,
     },
     Delete {
         language_pair: String,
     },
-    Translate {
+    List {
+        /// An array of objects instead of a line per database
+        json: bool,
+    },
+    ExportWords {
+        language_pair: String,
+        language_from: String,
+    },
+    Favorites {
+        language_pair: String,
+        output_format: OutputFormat,
+    },
+    History {
+        language_pair: String,
+        limit: Option<u32>,
+        clear: bool,
+    },
+    Verify {
+        language_pair: String,
+    },
+    Warmup {
         language_pair: String,
+    },
+    Diff {
+        database_old: String,
+        database_new: String,
+        output_format: OutputFormat,
+    },
+    SearchAll {
+        query: String,
+        fuzzy_distance: u8,
+        output_format: OutputFormat,
+    },
+    Translate {
+        language_pairs: Vec<String>,
         language_from: String,
         fuzzy_distance: u8,
+        max_results_before_tighten: Option<u32>,
+        max_fuzzy_expansions: Option<u32>,
+        autocorrect: bool,
+        fuzzy_mode: FuzzyMode,
         limit_results: Option<u32>,
-        minimum_similarity: Option<u16>,
+        skip_results: Option<u32>,
+        tail_results: Option<u32>,
+        search_memory_budget: bool,
+        search_extra: bool,
+        include_reverse: bool,
+        /// From 0 to 1
+        minimum_similarity: Option<f64>,
+        min_word_length: Option<usize>,
+        rank: Rank,
+        no_sort: bool,
+        explain: bool,
+        print_query: bool,
+        dump: bool,
+        output_format: OutputFormat,
+        swap_columns: bool,
+        /// The columns of the result table, instead of the source, target and enabled columns
+        column_order: Option<Vec<TableColumn>>,
+        /// Alignment of the cells of the result table
+        align: CellAlignment,
+        show_source: bool,
+        target_only: bool,
+        show_extra: bool,
+        show_phonetic: bool,
+        highlight: bool,
+        separate_exact: bool,
+        footer: bool,
+        literal_tokens: bool,
+        case_sensitive: bool,
+        contains: bool,
+        exact_extra: bool,
         completion_type: rustyline::config::CompletionType,
-        ascii: bool,
+        bell_style: rustyline::config::BellStyle,
+        tab_stop: usize,
+        /// The comfy-table preset given with --preset or --ascii
+        table_preset: Option<&'static str>,
+        force_table: bool,
+        width: Option<u16>,
+        tui: bool,
+        select: bool,
+        /// Fail with `NoResults` if a search given as argument finds nothing
+        exit_on_empty: bool,
+        /// Read the translation aloud, see `speech::speak`
+        speak: bool,
+        stdin_queries: bool,
+        random: Option<u32>,
+        regex_full: bool,
+        search_field: Option<LabelField>,
+        back_translate: Option<u32>,
         search: Option<String>,
     },
 }
 
 pub(crate) fn parse_settings() -> Result<Settings, DictCliError> {
-    let args = parse_args();
+    let available_language_pairs = database::available_language_pairs();
+    let default_selection = default_selection(available_language_pairs.as_deref(), state::last_selection());
+    let args = parse_args(available_language_pairs.as_deref(), default_selection.as_ref())?;
+    let color_choice = match args
+        .subcommand()
+        .map_or(&args, |(_, subcommand_args)| subcommand_args)
+        .get_one::<String>("color")
+        .unwrap()
+        .as_str()
+    {
+        "auto" => ColorChoice::Auto,
+        "always" => ColorChoice::Always,
+        "never" => ColorChoice::Never,
+        _ => unreachable!(),
+    };
+    style::set_color_choice(color_choice);
+    error::set_json_errors(
+        args.subcommand()
+            .map_or(&args, |(_, subcommand_args)| subcommand_args)
+            .get_one::<String>("error-format")
+            .unwrap()
+            == "json",
+    );
 
     if let Some(import) = args.subcommand_matches("import") {
         return Ok(Settings::Import {
             file: import.get_one::<PathBuf>("FILE").unwrap().to_owned(),
-            force: import.get_flag("force"),
+            options: ImportOptions {
+                force: import.get_flag("force"),
+                delimiter: *import.get_one::<u8>("delimiter").unwrap(),
+                lossy: import.get_flag("lossy"),
+                frequency_files: import
+                    .get_many::<PathBuf>("frequency-file")
+                    .map(|files| files.cloned().collect())
+                    .unwrap_or_default(),
+                no_extra_index: import.get_flag("no-extra-index"),
+                html_decode: !import.get_flag("no-html-decode"),
+                index_collapsed: import.get_flag("index-collapsed"),
+                index_cased: import.get_flag("index-cased"),
+                index_ngram: import.get_flag("ngram"),
+                dedup: import.get_flag("dedup"),
+                no_precount: import.get_flag("no-precount"),
+                normalization_cache: import.get_flag("normalization-cache"),
+                summary: import.get_one::<u32>("summary").map(|count| *count as usize),
+                max_entry_len: import.get_one::<u32>("max-entry-len").map(|len| *len as usize),
+                format: import.get_one::<String>("format").map(|format| match format.as_str() {
+                    "dictcc" => ImportFormat::Dictcc,
+                    "vocab" => ImportFormat::Vocab,
+                    _ => unreachable!(),
+                }),
+                tokenizer: TokenizerKind::from_name(import.get_one::<String>("tokenizer").unwrap()).unwrap(),
+            },
         });
     }
 
@@ -42,17 +218,106 @@ pub(crate) fn parse_settings() -> Result<Settings, DictCliError> {
         });
     }
 
-    let language_pair = args.get_one::<String>("language-pair").unwrap().to_lowercase();
-    let language_from = args.get_one::<String>("from").unwrap().to_lowercase();
-    let languages = database::languages(&language_pair)?;
+    if let Some(list) = args.subcommand_matches("list") {
+        return Ok(Settings::List {
+            json: list.get_one::<String>("format").unwrap() == "json",
+        });
+    }
 
-    if language_from != languages.0 && language_from != languages.1 {
-        return Err(DictCliError::SearchLanguageNotAvailable(
-            language_from,
-            format!("{}, {}", languages.0, languages.1),
-        ));
+    if let Some(export_words) = args.subcommand_matches("export-words") {
+        return Ok(Settings::ExportWords {
+            language_pair: export_words.get_one::<String>("LANGUAGE_PAIR").unwrap().to_lowercase(),
+            language_from: language::resolve(export_words.get_one::<String>("from").unwrap()),
+        });
+    }
+
+    if let Some(favorites) = args.subcommand_matches("favorites") {
+        return Ok(Settings::Favorites {
+            language_pair: favorites.get_one::<String>("LANGUAGE_PAIR").unwrap().to_lowercase(),
+            output_format: output_format(favorites.get_one::<String>("format").unwrap()),
+        });
+    }
+
+    if let Some(history) = args.subcommand_matches("history") {
+        return Ok(Settings::History {
+            language_pair: history.get_one::<String>("LANGUAGE_PAIR").unwrap().to_lowercase(),
+            limit: history.get_one::<u32>("limit").copied(),
+            clear: history.get_flag("clear"),
+        });
+    }
+
+    if let Some(verify) = args.subcommand_matches("verify") {
+        return Ok(Settings::Verify {
+            language_pair: verify.get_one::<String>("LANGUAGE_PAIR").unwrap().to_lowercase(),
+        });
+    }
+
+    if let Some(warmup) = args.subcommand_matches("warmup") {
+        return Ok(Settings::Warmup {
+            language_pair: warmup.get_one::<String>("LANGUAGE_PAIR").unwrap().to_lowercase(),
+        });
+    }
+
+    if let Some(diff) = args.subcommand_matches("diff") {
+        return Ok(Settings::Diff {
+            database_old: diff.get_one::<String>("OLD").unwrap().to_owned(),
+            database_new: diff.get_one::<String>("NEW").unwrap().to_owned(),
+            output_format: output_format(diff.get_one::<String>("format").unwrap()),
+        });
     }
 
+    if let Some(search_all) = args.subcommand_matches("search-all") {
+        return Ok(Settings::SearchAll {
+            query: search_all.get_one::<String>("QUERY").unwrap().to_owned(),
+            fuzzy_distance: *search_all.get_one::<u8>("distance").unwrap(),
+            output_format: output_format(search_all.get_one::<String>("format").unwrap()),
+        });
+    }
+
+    let language_pairs: Vec<String> = match args.get_many::<String>("language-pair") {
+        Some(language_pairs) => language_pairs
+            .map(|language_pair| language_pair.to_lowercase())
+            .unique()
+            .collect(),
+        None => match &default_selection {
+            Some(default_selection) => vec![default_selection.language_pair.clone()],
+            None => return Err(DictCliError::MissingArgument("--language-pair")),
+        },
+    };
+    let language_from = match args.get_one::<String>("from") {
+        Some(language_from) => language::resolve(language_from),
+        None => match &default_selection {
+            Some(default_selection) if language_pairs == [default_selection.language_pair.as_str()] => {
+                default_selection.language_from.clone()
+            }
+            _ => return Err(DictCliError::MissingArgument("--from")),
+        },
+    };
+
+    for language_pair in language_pairs.iter() {
+        let languages = database::languages(language_pair)?;
+
+        if language_from != languages.0 && language_from != languages.1 {
+            return Err(DictCliError::SearchLanguageNotAvailable(
+                language_from,
+                vec![languages.0.to_owned(), languages.1.to_owned()],
+            ));
+        }
+    }
+
+    let rank = match args.get_one::<String>("rank").unwrap().as_str() {
+        "similarity" => Rank::Similarity,
+        "frequency" => Rank::Frequency,
+        "word-classes" => Rank::WordClasses,
+        _ => unreachable!(),
+    };
+
+    let output_format = match args.get_one::<Template>("template") {
+        Some(template) => OutputFormat::Template(template.clone()),
+        None if args.get_flag("first") => OutputFormat::First,
+        None => output_format(args.get_one::<String>("format").unwrap()),
+    };
+
     let completion_type = match args
         .get_one::<String>("completion-type")
         .unwrap()
@@ -64,19 +329,206 @@ pub(crate) fn parse_settings() -> Result<Settings, DictCliError> {
         _ => unreachable!(),
     };
 
+    let bell_style = match args.get_one::<String>("bell").unwrap().as_str() {
+        "none" => rustyline::config::BellStyle::None,
+        "audible" => rustyline::config::BellStyle::Audible,
+        "visible" => rustyline::config::BellStyle::Visible,
+        _ => unreachable!(),
+    };
+
     Ok(Settings::Translate {
-        language_pair: args.get_one::<String>("language-pair").unwrap().to_lowercase(),
-        language_from: args.get_one::<String>("from").unwrap().to_lowercase(),
+        language_pairs,
+        language_from,
         fuzzy_distance: *args.get_one::<u8>("distance").unwrap(),
+        max_results_before_tighten: args.get_one::<u32>("max-results-before-tighten").copied(),
+        max_fuzzy_expansions: args.get_one::<u32>("max-fuzzy-expansions").copied(),
+        autocorrect: args.get_flag("autocorrect"),
+        fuzzy_mode: match args.get_one::<String>("fuzzy-mode").unwrap().as_str() {
+            "per-token" => FuzzyMode::PerToken,
+            "total" => FuzzyMode::Total,
+            _ => unreachable!(),
+        },
         limit_results: args.get_one::<u32>("limit-results").copied(),
-        minimum_similarity: args.get_one::<u16>("min-similarity").copied(),
+        skip_results: args.get_one::<u32>("skip").copied(),
+        tail_results: args.get_one::<u32>("tail").copied(),
+        search_memory_budget: args.get_flag("search-memory-budget"),
+        search_extra: !args.get_flag("no-extra"),
+        include_reverse: args.get_flag("include-reverse"),
+        minimum_similarity: args.get_one::<f64>("min-similarity").copied(),
+        min_word_length: args.get_one::<u32>("min-word-length").map(|length| *length as usize),
+        rank,
+        no_sort: args.get_flag("no-sort"),
+        explain: args.get_flag("explain"),
+        print_query: args.get_flag("print-query"),
+        dump: args.get_flag("dump"),
+        output_format,
+        swap_columns: args.get_flag("swap-columns"),
+        column_order: args
+            .get_many::<String>("column-order")
+            .map(|columns| columns.map(|column| table_column(column)).collect()),
+        align: match args.get_one::<String>("align").unwrap().as_str() {
+            "left" => CellAlignment::Left,
+            "right" => CellAlignment::Right,
+            _ => unreachable!(),
+        },
+        show_source: args.get_flag("show-source"),
+        target_only: args.get_flag("target-only"),
+        show_extra: args.get_flag("show-extra"),
+        show_phonetic: args.get_flag("show-phonetic"),
+        highlight: args.get_flag("highlight"),
+        separate_exact: args.get_flag("separate-exact"),
+        footer: args.get_flag("footer"),
+        literal_tokens: args.get_flag("literal-tokens"),
+        case_sensitive: args.get_flag("case-sensitive"),
+        contains: args.get_flag("contains"),
+        exact_extra: args.get_flag("exact-extra"),
         completion_type,
-        ascii: args.get_flag("ascii"),
+        bell_style,
+        tab_stop: *args.get_one::<u8>("tab-stop").unwrap() as usize,
+        table_preset: if args.get_flag("ascii") {
+            Some(ASCII_FULL)
+        } else {
+            args.get_one::<String>("preset").map(|preset| table_preset(preset))
+        },
+        force_table: args.get_flag("force-table"),
+        width: args.get_one::<u16>("width").copied(),
+        tui: args.get_flag("tui"),
+        select: args.get_flag("select"),
+        exit_on_empty: args.get_flag("exit-on-empty"),
+        speak: args.get_flag("speak"),
+        stdin_queries: args.get_flag("stdin-queries"),
+        random: args.get_one::<u32>("random").copied(),
+        regex_full: args.get_flag("regex-full"),
+        search_field: args
+            .get_one::<String>("search-field")
+            .map(|search_field| match search_field.as_str() {
+                "word-class" => LabelField::WordClasses,
+                "subject" => LabelField::SubjectLabels,
+                _ => unreachable!(),
+            }),
+        back_translate: args.get_one::<u32>("back-translate").copied(),
         search: args.get_one::<String>("SEARCH").map(|search| search.to_owned()),
     })
 }
 
-fn parse_args() -> ArgMatches {
+fn table_column(column: &str) -> TableColumn {
+    match column {
+        "source" => TableColumn::Source,
+        "target" => TableColumn::Target,
+        "word_classes" | "word_class" => TableColumn::WordClasses,
+        "subject_labels" | "subject" => TableColumn::SubjectLabels,
+        "extra" => TableColumn::Extra,
+        "phonetic" => TableColumn::Phonetic,
+        "source_file" => TableColumn::SourceFile,
+        _ => unreachable!(),
+    }
+}
+
+fn output_format(format: &str) -> OutputFormat {
+    match format {
+        "table" => OutputFormat::Table,
+        "json" => OutputFormat::Json,
+        "jsonl" => OutputFormat::JsonLines,
+        "anki" => OutputFormat::Anki,
+        _ => unreachable!(),
+    }
+}
+
+fn table_preset(preset: &str) -> &'static str {
+    match preset {
+        "utf8-full" => UTF8_FULL,
+        "borders-only" => UTF8_BORDERS_ONLY,
+        "horizontal-only" => UTF8_HORIZONTAL_ONLY,
+        "no-borders" => UTF8_NO_BORDERS,
+        "ascii-full" => ASCII_FULL,
+        "ascii-borders-only" => ASCII_BORDERS_ONLY,
+        "ascii-horizontal-only" => ASCII_HORIZONTAL_ONLY,
+        "ascii-no-borders" => ASCII_NO_BORDERS,
+        "markdown" => ASCII_MARKDOWN,
+        "nothing" => NOTHING,
+        _ => unreachable!(),
+    }
+}
+
+fn parse_delimiter(value: &str) -> Result<u8, String> {
+    match value {
+        "\\t" => Ok(b'\t'),
+        _ if value.len() == 1 => Ok(value.as_bytes()[0]),
+        _ => Err(String::from("the delimiter must be a single ASCII character")),
+    }
+}
+
+/// Scales a similarity from 0 to 1000 like the scores of `--explain`, or takes one with a decimal point from 0.0 to 1.0
+/// as it is, which is finer than the scaled scores
+fn parse_similarity(value: &str) -> Result<f64, String> {
+    if value.contains('.') {
+        match value.parse::<f64>() {
+            Ok(similarity) if (0.0..=1.0).contains(&similarity) => Ok(similarity),
+            _ => Err(String::from(
+                "a similarity with a decimal point must be from 0.0 to 1.0",
+            )),
+        }
+    } else {
+        match value.parse::<u16>() {
+            Ok(similarity) if similarity <= 1000 => Ok(f64::from(similarity) / 1000.0),
+            _ => Err(String::from("a similarity must be from 0 to 1000")),
+        }
+    }
+}
+
+/// Accepts the available languages by code, three-letter code, regional code or name
+#[derive(Clone)]
+struct LanguageValueParser(PossibleValuesParser);
+
+impl LanguageValueParser {
+    fn new(languages: &[String]) -> Self {
+        Self(PossibleValuesParser::new(languages.iter().map(|lang| {
+            PossibleValue::new(lang.clone()).aliases(language::aliases(lang))
+        })))
+    }
+}
+
+impl TypedValueParser for LanguageValueParser {
+    type Value = String;
+
+    fn parse_ref(&self, cmd: &Command, arg: Option<&Arg>, value: &OsStr) -> Result<Self::Value, clap::Error> {
+        match value.to_str() {
+            Some(value) => self.0.parse_ref(cmd, arg, OsStr::new(&language::resolve(value))),
+            None => self.0.parse_ref(cmd, arg, value),
+        }
+    }
+
+    fn possible_values(&self) -> Option<Box<dyn Iterator<Item = PossibleValue> + '_>> {
+        self.0.possible_values()
+    }
+}
+
+/// The last selection, or the only imported language pair from its left language
+fn default_selection(
+    available_language_pairs: Option<&[String]>,
+    last_selection: Option<LastSelection>,
+) -> Option<LastSelection> {
+    let available_language_pairs = available_language_pairs?;
+
+    if let Some(last_selection) = last_selection {
+        if available_language_pairs.contains(&last_selection.language_pair) {
+            return Some(last_selection);
+        }
+    }
+
+    match available_language_pairs {
+        [language_pair] => Some(LastSelection {
+            language_pair: language_pair.clone(),
+            language_from: database::languages(language_pair).ok()?.0.to_owned(),
+        }),
+        _ => None,
+    }
+}
+
+fn parse_args(
+    available_language_pairs: Option<&[String]>,
+    default_selection: Option<&LastSelection>,
+) -> Result<ArgMatches, DictCliError> {
     let mut command = Command::new(crate_name!()).version(crate_version!());
     let description = crate_description!();
 
@@ -84,13 +536,28 @@ fn parse_args() -> ArgMatches {
         command = command.about(description);
     }
 
-    let available_language_pairs = database::available_language_pairs();
-    let available_languages = available_language_pairs
-        .as_ref()
-        .map(|lang_pairs| database::available_languages(lang_pairs));
+    let available_languages = available_language_pairs.map(database::available_languages);
 
-    command
+    let command = command
         .args_conflicts_with_subcommands(true)
+        .arg(
+            arg!(
+                --color <WHEN> "When to use colors"
+            )
+            .required(false)
+            .global(true)
+            .value_parser(["auto", "always", "never"])
+            .default_value("auto"),
+        )
+        .arg(
+            arg!(
+                --"error-format" <FORMAT> "How to print errors, json prints an object with a stable code, the message and its context"
+            )
+            .required(false)
+            .global(true)
+            .value_parser(["text", "json"])
+            .default_value("text"),
+        )
         .subcommand(
             Command::new("import")
                 .about("Import a dict.cc file")
@@ -100,6 +567,107 @@ fn parse_args() -> ArgMatches {
                     )
                     .required(false),
                 )
+                .arg(
+                    arg!(
+                        --delimiter <CHAR> "Column delimiter of the file, use \\t for tabs"
+                    )
+                    .required(false)
+                    .value_parser(parse_delimiter)
+                    .default_value("\\t"),
+                )
+                .arg(
+                    arg!(
+                        --lossy "Replace invalid UTF-8 instead of skipping the line"
+                    )
+                    .required(false),
+                )
+                .arg(
+                    arg!(
+                        --"frequency-file" <FILE> "Word frequency list with one \"word count\" pair per line, for --rank frequency"
+                    )
+                    .required(false)
+                    .action(ArgAction::Append)
+                    .value_parser(PathBufValueParser::new()),
+                )
+                .arg(
+                    arg!(
+                        --"no-extra-index" "Do not index the grammatical annotations in angle brackets to save space"
+                    )
+                    .required(false),
+                )
+                .arg(
+                    arg!(
+                        --"no-html-decode" "Store HTML entities like &amp; as they are instead of decoding them"
+                    )
+                    .required(false),
+                )
+                .arg(
+                    arg!(
+                        --"index-collapsed" "Also index the entries without spaces, so that e.g. icecream finds ice cream and the other way around"
+                    )
+                    .required(false),
+                )
+                .arg(
+                    arg!(
+                        --"index-cased" "Also index the entries with their case, for searches with --case-sensitive"
+                    )
+                    .required(false),
+                )
+                .arg(
+                    arg!(
+                        --ngram "Also index the parts of two or three characters of the entries, for fast searches with --contains"
+                    )
+                    .required(false),
+                )
+                .arg(
+                    arg!(
+                        --dedup "Skip entries with the same source and target as an earlier one, needs some memory for every entry"
+                    )
+                    .required(false),
+                )
+                .arg(
+                    arg!(
+                        --"no-precount" "Read the file only once instead of counting the entries first, the progress shows no total then"
+                    )
+                    .required(false),
+                )
+                .arg(
+                    arg!(
+                        --"normalization-cache" "Keep the parsed entries next to the databases, so that importing the same file again skips parsing them"
+                    )
+                    .required(false),
+                )
+                .arg(
+                    arg!(
+                        --summary [COUNT] "Print the most common word classes and subject labels after the import, --summary=COUNT sets how many [default: 10]"
+                    )
+                    .required(false)
+                    .value_parser(clap::value_parser!(u32).range(1..))
+                    .require_equals(true)
+                    .default_missing_value("10"),
+                )
+                .arg(
+                    arg!(
+                        --"max-entry-len" <CHARS> "Skip entries whose source or target is longer, e.g. whole example sentences"
+                    )
+                    .required(false)
+                    .value_parser(clap::value_parser!(u32).range(1..)),
+                )
+                .arg(
+                    arg!(
+                        --format <FORMAT> "Layout of the file, a full translation file or a vocabulary list. Detected by the first line if not given"
+                    )
+                    .required(false)
+                    .value_parser(["dictcc", "vocab"]),
+                )
+                .arg(
+                    arg!(
+                        --tokenizer <TOKENIZER> "How to split the entries, ngram finds words inside of words and whitespace keeps e.g. don't as one word"
+                    )
+                    .required(false)
+                    .value_parser(["simple", "ngram", "whitespace"])
+                    .default_value("simple"),
+                )
                 .arg(
                     arg!(
                         <FILE> "dict.cc file from https://www1.dict.cc/translation_file_request.php"
@@ -117,80 +685,654 @@ fn parse_args() -> ArgMatches {
                     )
                     .ignore_case(true)
                     .required(true);
-                    if let Some(langs) = available_language_pairs.as_ref() {
+                    if let Some(langs) = available_language_pairs {
                         arg.value_parser(PossibleValuesParser::new(langs.iter()))
                     } else {
                         arg.value_parser(NonEmptyStringValueParser::new())
                     }
                 }),
         )
-        .arg({
-            let arg = arg!(
-                -l --"language-pair" <LANGUAGE_PAIR> "Languages to translate between"
-            )
-            .ignore_case(true)
-            .required(true);
-            if let Some(langs) = available_language_pairs.as_ref() {
-                arg.value_parser(PossibleValuesParser::new(langs.iter()))
-            } else {
-                arg.value_parser(NonEmptyStringValueParser::new())
-            }
-        })
-        .arg({
-            let arg = arg!(
-                -f --from <LANGUAGE> "The source language to translate from"
-            )
-            .ignore_case(true)
-            .required(true);
-            if let Some(langs) = available_languages.as_ref() {
-                arg.value_parser(PossibleValuesParser::new(langs.iter()))
-            } else {
-                arg.value_parser(NonEmptyStringValueParser::new())
-            }
-        })
-        .arg(
-            arg!(
-                -d --distance <DISTANCE> "Fuzzy distance to find entries"
-            )
-            .required(false)
-            .value_parser(clap::value_parser!(u8))
-            .default_value("0"),
+        .subcommand(
+            Command::new("list").about("List the imported dict.cc databases").arg(
+                arg!(
+                    --format <FORMAT> "Output format, json prints an array with an object per database"
+                )
+                .required(false)
+                .value_parser(["text", "json"])
+                .default_value("text"),
+            ),
         )
-        .arg(
-            arg!(
-                -r --"limit-results" <LIMIT> "Limit the amount of results"
-            )
-            .required(false)
-            .value_parser(clap::value_parser!(u32).range(1..)),
+        .subcommand(
+            Command::new("export-words")
+                .about("Print every word of a language in a database, e.g. for spell checkers")
+                .arg({
+                    let arg = arg!(
+                        <LANGUAGE_PAIR> "The language pair of the database"
+                    )
+                    .ignore_case(true)
+                    .required(true);
+                    if let Some(langs) = available_language_pairs {
+                        arg.value_parser(PossibleValuesParser::new(langs.iter()))
+                    } else {
+                        arg.value_parser(NonEmptyStringValueParser::new())
+                    }
+                })
+                .arg(
+                    arg!(
+                        -f --from <LANGUAGE> "The language of the words"
+                    )
+                    .required(true)
+                    .value_parser(NonEmptyStringValueParser::new()),
+                ),
         )
-        .arg(
-            arg!(
-                -s --"min-similarity" <LIMIT> "Only show results with a specific minimum of similarity [possible values: 0 to 1000]"
-            )
-            .required(false)
-            .value_parser(clap::value_parser!(u16).range(0..=1000)),
+        .subcommand(
+            Command::new("favorites")
+                .about("List the favorites saved with :fav in interactive mode")
+                .arg({
+                    let arg = arg!(
+                        <LANGUAGE_PAIR> "The language pair of the favorites"
+                    )
+                    .ignore_case(true)
+                    .required(true);
+                    if let Some(langs) = available_language_pairs {
+                        arg.value_parser(PossibleValuesParser::new(langs.iter()))
+                    } else {
+                        arg.value_parser(NonEmptyStringValueParser::new())
+                    }
+                })
+                .arg(
+                    arg!(
+                        --format <FORMAT> "Output format, json prints one object and jsonl one object per favorite, anki one flashcard per favorite"
+                    )
+                    .required(false)
+                    .value_parser(["table", "json", "jsonl", "anki"])
+                    .default_value("table"),
+                ),
         )
-        .arg(
-            arg!(
-                -c --"completion-type" <TYPE> "Tab completion style"
-            )
-            .required(false)
-            .ignore_case(true)
-            .value_parser(["circular", "list"])
-            .default_value("list"),
+        .subcommand(
+            Command::new("history")
+                .about("List the recent searches of the interactive mode")
+                .arg({
+                    let arg = arg!(
+                        <LANGUAGE_PAIR> "The language pair of the searches"
+                    )
+                    .ignore_case(true)
+                    .required(true);
+                    if let Some(langs) = available_language_pairs {
+                        arg.value_parser(PossibleValuesParser::new(langs.iter()))
+                    } else {
+                        arg.value_parser(NonEmptyStringValueParser::new())
+                    }
+                })
+                .arg(
+                    arg!(
+                        -n --limit <COUNT> "Only list the most recent searches"
+                    )
+                    .required(false)
+                    .value_parser(clap::value_parser!(u32).range(1..)),
+                )
+                .arg(
+                    arg!(
+                        --clear "Delete the searches instead of listing them"
+                    )
+                    .required(false)
+                    .conflicts_with("limit"),
+                ),
+        )
+        .subcommand(
+            Command::new("verify")
+                .about("Check that every entry of a database is readable, fails if it is corrupted")
+                .arg({
+                    let arg = arg!(
+                        <LANGUAGE_PAIR> "The language pair of the database"
+                    )
+                    .ignore_case(true)
+                    .required(true);
+                    if let Some(langs) = available_language_pairs {
+                        arg.value_parser(PossibleValuesParser::new(langs.iter()))
+                    } else {
+                        arg.value_parser(NonEmptyStringValueParser::new())
+                    }
+                }),
+        )
+        .subcommand(
+            Command::new("warmup")
+                .about("Read a database into the page cache of the system, so that the first searches are fast too")
+                .arg({
+                    let arg = arg!(
+                        <LANGUAGE_PAIR> "The language pair of the database"
+                    )
+                    .ignore_case(true)
+                    .required(true);
+                    if let Some(langs) = available_language_pairs {
+                        arg.value_parser(PossibleValuesParser::new(langs.iter()))
+                    } else {
+                        arg.value_parser(NonEmptyStringValueParser::new())
+                    }
+                }),
+        )
+        .subcommand(
+            Command::new("diff")
+                .about("Show the entries added and removed between two databases, e.g. an old and a new export")
+                .arg(
+                    arg!(
+                        <OLD> "The old database, an imported language pair or a database directory"
+                    )
+                    .value_parser(NonEmptyStringValueParser::new()),
+                )
+                .arg(
+                    arg!(
+                        <NEW> "The new database, an imported language pair or a database directory"
+                    )
+                    .value_parser(NonEmptyStringValueParser::new()),
+                )
+                .arg(
+                    arg!(
+                        --format <FORMAT> "Output format, json prints one object and jsonl one object per entry"
+                    )
+                    .required(false)
+                    .value_parser(["table", "json", "jsonl"])
+                    .default_value("table"),
+                ),
+        )
+        .subcommand(
+            Command::new("search-all")
+                .about("Search every imported language pair in both directions, e.g. for a word of an unknown language")
+                .arg(
+                    arg!(
+                        <QUERY> "The word or words to search for"
+                    )
+                    .value_parser(NonEmptyStringValueParser::new()),
+                )
+                .arg(
+                    arg!(
+                        -d --distance <DISTANCE> "Fuzzy distance to find entries, at most 2"
+                    )
+                    .required(false)
+                    .value_parser(clap::value_parser!(u8).range(..=i64::from(database::MAX_FUZZY_DISTANCE)))
+                    .default_value("0"),
+                )
+                .arg(
+                    arg!(
+                        --format <FORMAT> "Output format, json prints one object and jsonl one object per result"
+                    )
+                    .required(false)
+                    .value_parser(["table", "json", "jsonl"])
+                    .default_value("table"),
+                ),
+        )
+        .arg({
+            let arg = arg!(
+                -l --"language-pair" <LANGUAGE_PAIR> "Languages to translate between, repeat or separate by commas to search several pairs with the same source language"
+            )
+            .ignore_case(true)
+            .action(ArgAction::Append)
+            .value_delimiter(',')
+            .required(default_selection.is_none());
+            if let Some(langs) = available_language_pairs {
+                arg.value_parser(PossibleValuesParser::new(langs.iter()))
+            } else {
+                arg.value_parser(NonEmptyStringValueParser::new())
+            }
+        })
+        .arg({
+            let arg = arg!(
+                -f --from <LANGUAGE> "The source language to translate from"
+            )
+            .ignore_case(true)
+            .required(default_selection.is_none());
+            if let Some(langs) = available_languages.as_ref() {
+                arg.value_parser(LanguageValueParser::new(langs))
+            } else {
+                arg.value_parser(NonEmptyStringValueParser::new())
+            }
+        })
+        .arg(
+            arg!(
+                -d --distance <DISTANCE> "Fuzzy distance to find entries, at most 2"
+            )
+            .required(false)
+            .value_parser(clap::value_parser!(u8).range(..=i64::from(database::MAX_FUZZY_DISTANCE)))
+            .default_value("0"),
+        )
+        .arg(
+            arg!(
+                --"max-results-before-tighten" <COUNT> "Search exact instead if a fuzzy search returns more results"
+            )
+            .required(false)
+            .value_parser(clap::value_parser!(u32).range(1..)),
+        )
+        .arg(
+            arg!(
+                --"max-fuzzy-expansions" <COUNT> "Lower the fuzzy distance of a word that matches more words of the database, to save memory on large databases"
+            )
+            .required(false)
+            .value_parser(clap::value_parser!(u32).range(1..)),
+        )
+        .arg(
+            arg!(
+                --"fuzzy-mode" <MODE> "Whether every word may differ by the fuzzy distance or all words together"
+            )
+            .required(false)
+            .value_parser(["per-token", "total"])
+            .default_value("per-token"),
         )
         .arg(
             arg!(
-                --ascii "Use ASCII tables"
+                --autocorrect "Replace the words that are in no entry by the most common word with the fewest changes before searching"
+            )
+            .required(false)
+            .conflicts_with_all(["literal-tokens", "case-sensitive", "contains", "exact-extra", "regex-full", "search-field"]),
+        )
+        .arg(
+            arg!(
+                -r --"limit-results" <LIMIT> "Limit the amount of results"
+            )
+            .required(false)
+            .value_parser(clap::value_parser!(u32).range(1..)),
+        )
+        .arg(
+            arg!(
+                --skip <COUNT> "Skip the first results, e.g. to page through them with --limit-results"
+            )
+            .required(false)
+            .value_parser(clap::value_parser!(u32)),
+        )
+        .arg(
+            arg!(
+                --tail <COUNT> "Only show the last results, after --skip and --limit-results"
+            )
+            .required(false)
+            .value_parser(clap::value_parser!(u32).range(1..))
+            .conflicts_with("search-memory-budget"),
+        )
+        .arg(
+            arg!(
+                --"search-memory-budget" "Only load the most similar documents up to the result limit to reduce memory usage"
+            )
+            .required(false)
+            .requires("limit-results"),
+        )
+        .arg(
+            arg!(
+                --"no-extra" "Do not search the grammatical annotations in angle brackets"
+            )
+            .required(false),
+        )
+        .arg(
+            arg!(
+                --"include-reverse" "Also search the target language if there are few results, marked with ←"
             )
             .required(false),
         )
+        .arg(
+            arg!(
+                -s --"min-similarity" <LIMIT> "Only show results with a specific minimum of similarity, from 0 to 1000 or with a decimal point from 0.0 to 1.0"
+            )
+            .required(false)
+            .value_parser(parse_similarity),
+        )
+        .arg(
+            arg!(
+                --"min-word-length" <CHARS> "Only show results whose entry is at least this long, e.g. to leave out short fuzzy matches"
+            )
+            .required(false)
+            .value_parser(clap::value_parser!(u32).range(1..)),
+        )
+        .arg(
+            arg!(
+                --rank <RANK> "How to order the results, frequency needs an import with --frequency-file, word-classes groups e.g. the nouns and the verbs"
+            )
+            .required(false)
+            .value_parser(["similarity", "frequency", "word-classes"])
+            .default_value("similarity"),
+        )
+        .arg(
+            arg!(
+                --"no-sort" "Show the results in the order of the index without scoring them, e.g. to profile the search"
+            )
+            .required(false)
+            .conflicts_with_all(["min-similarity", "min-word-length", "rank", "explain"]),
+        )
+        .arg(
+            arg!(
+                --explain "Print how each result was scored to stderr"
+            )
+            .required(false),
+        )
+        .arg(
+            arg!(
+                --dump "Print every stored field of each result to stderr, e.g. the normalized search keys"
+            )
+            .required(false),
+        )
+        .arg(
+            arg!(
+                --"print-query" "Print the queries of each search to stderr, with the searched tokens and fuzzy distances"
+            )
+            .required(false)
+            .conflicts_with_all(["regex-full", "search-field"]),
+        )
+        .arg(
+            arg!(
+                --format <FORMAT> "Output format, json prints one object per search and jsonl one object per result, anki one flashcard per result"
+            )
+            .required(false)
+            .value_parser(["table", "json", "jsonl", "anki"])
+            .default_value("table"),
+        )
+        .arg(
+            arg!(
+                --template <TEMPLATE> "Print each result as a line like \"{source} => {target}\", available are {source}, {target}, {word_classes}, {subject_labels} and {score}, {{ and }} are literal braces"
+            )
+            .required(false)
+            .conflicts_with("format")
+            .value_parser(Template::parse),
+        )
+        .arg(
+            arg!(
+                -'1' --first "Only print the target of the best result, fails if nothing is found"
+            )
+            .required(false)
+            .conflicts_with_all(["format", "template"]),
+        )
+        .arg(
+            arg!(
+                --"exit-on-empty" "Exit with code 4 if the search finds nothing, e.g. to branch in shell scripts"
+            )
+            .required(false)
+            .requires("SEARCH"),
+        )
+        .arg(
+            arg!(
+                --speak "Read the first translation, or the selected one with --select, aloud with the text-to-speech program of the system"
+            )
+            .required(false)
+            .requires("SEARCH"),
+        )
+        .arg(
+            arg!(
+                -c --"completion-type" <TYPE> "Tab completion style"
+            )
+            .required(false)
+            .ignore_case(true)
+            .value_parser(["circular", "list"])
+            .default_value("list"),
+        )
+        .arg(
+            arg!(
+                --bell <STYLE> "Bell style of the interactive mode, e.g. when tab completion finds nothing"
+            )
+            .required(false)
+            .value_parser(["none", "audible", "visible"])
+            .default_value("none"),
+        )
+        .arg(
+            arg!(
+                --"tab-stop" <COLUMNS> "Tab width of the interactive mode"
+            )
+            .required(false)
+            .value_parser(clap::value_parser!(u8).range(1..))
+            .default_value("4"),
+        )
+        .arg(
+            arg!(
+                --"swap-columns" "Show the target language in the left column"
+            )
+            .required(false),
+        )
+        .arg(
+            arg!(
+                --"column-order" <COLUMNS> "The columns of the result table in this order, separated by commas, e.g. target,source,word_classes"
+            )
+            .required(false)
+            .value_delimiter(',')
+            .value_parser(PossibleValuesParser::new([
+                PossibleValue::new("source"),
+                PossibleValue::new("target"),
+                PossibleValue::new("word_classes").alias("word_class"),
+                PossibleValue::new("subject_labels").alias("subject"),
+                PossibleValue::new("extra"),
+                PossibleValue::new("phonetic"),
+                PossibleValue::new("source_file"),
+            ]))
+            .conflicts_with_all(["swap-columns", "target-only"]),
+        )
+        .arg(
+            arg!(
+                --align <ALIGNMENT> "Alignment of the cells of the result table"
+            )
+            .required(false)
+            .value_parser(["left", "right"])
+            .default_value("left"),
+        )
+        .arg(
+            arg!(
+                --"literal-tokens" "Match every space separated part of the search exactly, without fuzzy distance, quotes or exclusions"
+            )
+            .required(false)
+            .conflicts_with_all(["regex-full", "search-field"]),
+        )
+        .arg(
+            arg!(
+                --"case-sensitive" "Match the case of the words, e.g. for names and abbreviations, needs an import with --index-cased. Annotations are not searched then"
+            )
+            .required(false)
+            .conflicts_with_all(["literal-tokens", "regex-full", "search-field"]),
+        )
+        .arg(
+            arg!(
+                --contains "Find the words inside of other words, e.g. aus finds Haus. Fast for databases imported with --ngram"
+            )
+            .required(false)
+            .conflicts_with_all(["literal-tokens", "case-sensitive", "regex-full", "search-field"]),
+        )
+        .arg(
+            arg!(
+                --"exact-extra" "Find the entries with exactly this annotation in angle brackets, e.g. sth. finds <sth.> but not <sb./sth.>"
+            )
+            .required(false)
+            .conflicts_with_all(["literal-tokens", "case-sensitive", "contains", "regex-full", "search-field"]),
+        )
+        .arg(
+            arg!(
+                --highlight "Color the words of the results that match the search in tables, green if exact and yellow if fuzzy"
+            )
+            .required(false),
+        )
+        .arg(
+            arg!(
+                --"separate-exact" "Show the results that have every word of the search exactly first, in their own table"
+            )
+            .required(false),
+        )
+        .arg(
+            arg!(
+                --footer "Show the number of results under the table, and of all results if not all are shown"
+            )
+            .required(false),
+        )
+        .arg(
+            arg!(
+                --"show-source" "Show the name of the imported file each result comes from"
+            )
+            .required(false),
+        )
+        .arg(
+            arg!(
+                --"show-extra" "Show the grammatical annotations in angle brackets of the searched entry, which are searched too"
+            )
+            .required(false),
+        )
+        .arg(
+            arg!(
+                --"show-phonetic" "Show the pronunciation of the translation, if the entry has one like /haʊs/ or [haʊs]"
+            )
+            .required(false),
+        )
+        .arg(
+            arg!(
+                --"target-only" "Only show the translations in a single column, without duplicates"
+            )
+            .required(false)
+            .conflicts_with_all([
+                "format",
+                "template",
+                "first",
+                "show-source",
+                "show-extra",
+                "show-phonetic",
+                "back-translate",
+            ]),
+        )
+        .arg(
+            arg!(
+                --ascii "Use ASCII tables, deprecated alias of --preset ascii-full"
+            )
+            .required(false)
+            .conflicts_with("preset"),
+        )
+        .arg(
+            arg!(
+                --preset <PRESET> "Table border style, by default utf8-full in a terminal and ascii-no-borders otherwise"
+            )
+            .required(false)
+            .value_parser([
+                "utf8-full",
+                "borders-only",
+                "horizontal-only",
+                "no-borders",
+                "ascii-full",
+                "ascii-borders-only",
+                "ascii-horizontal-only",
+                "ascii-no-borders",
+                "markdown",
+                "nothing",
+            ]),
+        )
+        .arg(
+            arg!(
+                --"force-table" "Draw full tables even if stdout is not a terminal or colors are disabled"
+            )
+            .required(false),
+        )
+        .arg(
+            arg!(
+                --width <COLUMNS> "Table width, instead of the detected terminal width or the COLUMNS environment variable"
+            )
+            .required(false)
+            .value_parser(clap::value_parser!(u16).range(1..)),
+        )
+        .arg(
+            arg!(
+                --tui "Full-screen interactive mode with live results"
+            )
+            .required(false)
+            .conflicts_with("SEARCH"),
+        )
+        .arg(
+            arg!(
+                --random <COUNT> "Show random entries instead of searching, e.g. for vocabulary practice"
+            )
+            .required(false)
+            .conflicts_with_all(["SEARCH", "tui"])
+            .value_parser(clap::value_parser!(u32).range(1..)),
+        )
+        .arg(
+            arg!(
+                --"regex-full" "Match the search as a regex against the complete entry, slow because every entry is scanned"
+            )
+            .required(false)
+            .conflicts_with_all(["tui", "random"]),
+        )
+        .arg(
+            arg!(
+                --"search-field" <FIELD> "Search the word classes like noun or the subject labels like [med.] instead and show all matching entries"
+            )
+            .required(false)
+            .value_parser(["word-class", "subject"])
+            .conflicts_with_all(["tui", "random", "regex-full"]),
+        )
+        .arg(
+            arg!(
+                --"back-translate" [LIMIT] "Translate the targets of the first results back to tell meanings apart, --back-translate=LIMIT sets how many [default: 10]"
+            )
+            .required(false)
+            .value_parser(clap::value_parser!(u32).range(1..))
+            .require_equals(true)
+            .default_missing_value("10"),
+        )
+        .arg(
+            arg!(
+                --select "Pick one of the results from a numbered menu and print only its target, if stdout is a terminal"
+            )
+            .required(false)
+            .requires("SEARCH")
+            .conflicts_with_all(["format", "template", "first", "regex-full", "search-field"]),
+        )
+        .arg(
+            arg!(
+                --"stdin-queries" "Search each line read from stdin as it arrives, without interactive mode, e.g. for other programs"
+            )
+            .required(false)
+            .conflicts_with_all(["SEARCH", "tui", "random"]),
+        )
         .arg(
             arg!(
                 [SEARCH] "Search without interactive mode"
             )
             .required(false)
             .value_parser(NonEmptyStringValueParser::new()),
-        )
-        .get_matches()
+        );
+
+    let args = alias::expand_aliases(std::env::args_os().collect(), &command)?;
+    Ok(command.get_matches_from(args))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::DatabaseSearch;
+
+    #[test]
+    fn similarity_bounds() {
+        // Without a decimal point the value is on the scale of 0 to 1000, so 1 is 0.001 and not 1.0
+        for (value, similarity) in [
+            ("0", 0.0),
+            ("0.0", 0.0),
+            ("1", 0.001),
+            ("1.0", 1.0),
+            ("1000", 1.0),
+            ("0.25", 0.25),
+        ] {
+            assert_eq!(parse_similarity(value), Ok(similarity), "{}", value);
+        }
+        for value in ["1000.1", "1001", "-0.1", "-1", "1.5", "abc", ""] {
+            assert!(parse_similarity(value).is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn invalid_default_source_language() {
+        // Only the language pair of the stored selection is checked, e.g. if the state file was edited
+        let available_language_pairs = [String::from("de-en")];
+        let last_selection = LastSelection {
+            language_pair: String::from("de-en"),
+            language_from: String::from("fr"),
+        };
+        let selection = default_selection(Some(&available_language_pairs), Some(last_selection)).unwrap();
+        assert_eq!(selection.language_from, "fr");
+
+        // Checked before any search, like for the translate settings
+        let db_search =
+            DatabaseSearch::in_memory(&selection.language_pair, b"Haus\thouse\n", &ImportOptions::default()).unwrap();
+        assert!(matches!(
+            db_search.is_reverse_langs(&selection.language_from),
+            Err(DictCliError::SearchLanguageNotAvailable(..))
+        ));
+        assert!(matches!(
+            db_search.target_language(&selection.language_from),
+            Err(DictCliError::SearchLanguageNotAvailable(..))
+        ));
+    }
 }
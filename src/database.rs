@@ -1,22 +1,53 @@
-use std::collections::HashSet;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs::{File, OpenOptions};
-use std::io::{stdout, BufRead, BufReader, Write};
+use std::hash::{Hash, Hasher};
+use std::io::{stdout, BufRead, BufReader, BufWriter, ErrorKind, Lines, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
 use std::path::{Path, PathBuf};
+use std::str::Utf8Error;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 
+use csv::ByteRecord;
 use itertools::Itertools;
-use tantivy::collector::DocSetCollector;
-use tantivy::query::{BooleanQuery, FuzzyTermQuery, Occur, PhraseQuery, Query, RegexQuery, TermQuery};
+use levenshtein_automata::{Distance, LevenshteinAutomatonBuilder, DFA};
+use rand::seq::SliceRandom;
+use regex::Regex;
+use tantivy::collector::{CustomScorer, CustomSegmentScorer, DocSetCollector, TopDocs};
+use tantivy::directory::error::LockError;
+use tantivy::query::{BooleanQuery, FuzzyTermQuery, Occur, PhraseQuery, Query, RegexQuery, TermQuery, Weight};
 use tantivy::schema::{Field, IndexRecordOption, Schema, TextFieldIndexing, TextOptions, STORED, TEXT};
-use tantivy::tokenizer::{LowerCaser, RemoveLongFilter, SimpleTokenizer, TextAnalyzer};
-use tantivy::{doc, Document, Index, IndexReader, Term};
+use tantivy::store::StoreReader;
+use tantivy::tokenizer::{
+    BoxTokenStream, LowerCaser, NgramTokenizer, RemoveLongFilter, SimpleTokenizer, TextAnalyzer, Token, TokenFilter,
+    TokenStream, WhitespaceTokenizer,
+};
+use tantivy::{
+    doc, DocAddress, DocId, Document, Index, IndexReader, IndexWriter, ReloadPolicy, Searcher, SegmentReader,
+    TantivyError, Term,
+};
+use tantivy_fst::Automaton;
 use unicode_normalization::UnicodeNormalization;
 
+use crate::directory::ReadOnlyDirectory;
 use crate::error::DictCliError;
-use crate::parser;
+use crate::query::SearchExpression;
+use crate::{language, parser};
 
 pub(crate) struct DatabaseSchema {
     schema: Schema,
+    /// For the word classes and subject labels
     lowercase_tokenizer: TextAnalyzer,
+    /// For the entries, with the casing rules of their language, see `language::lowercase`
+    lowercase_tokenizer_left: TextAnalyzer,
+    lowercase_tokenizer_right: TextAnalyzer,
+    cased_tokenizer: TextAnalyzer,
+    ngram_tokenizer_left: TextAnalyzer,
+    ngram_tokenizer_right: TextAnalyzer,
     key_lang_left: Field,
     key_lang_right: Field,
     extra_lang_left: Field,
@@ -25,37 +56,168 @@ pub(crate) struct DatabaseSchema {
     pub(crate) lang_right: Field,
     pub(crate) word_classes: Field,
     pub(crate) subject_labels: Field,
+    pub(crate) frequency_lang_left: Field,
+    pub(crate) frequency_lang_right: Field,
+    /// The name of the imported file, empty for databases imported before it was recorded
+    pub(crate) source_file: Field,
+    /// The entries without spaces and punctuation, so that e.g. `icecream` finds `ice cream`
+    collapsed_lang_left: Field,
+    collapsed_lang_right: Field,
+    /// The keys without lowercasing, for `--case-sensitive`
+    cased_lang_left: Field,
+    cased_lang_right: Field,
+    /// The keys split into parts of two or three characters, for `--contains`
+    ngram_lang_left: Field,
+    ngram_lang_right: Field,
+    /// Every annotation as one term without lowercasing, for `--exact-extra`
+    exact_extra_lang_left: Field,
+    exact_extra_lang_right: Field,
+    /// Whether the angle bracket annotations are indexed, see `--no-extra-index`
+    index_extra: bool,
+    /// Whether the entries without spaces are indexed, see `--index-collapsed`
+    index_collapsed: bool,
+    /// Whether the keys are also indexed without lowercasing, see `--index-cased`
+    index_cased: bool,
+    /// Whether the n-grams of the keys are indexed, see `--ngram`
+    index_ngram: bool,
+    /// How the entries are split into tokens, see `--tokenizer`
+    tokenizer: TokenizerKind,
 }
 
 impl DatabaseSchema {
-    fn new(lang_left: &str, lang_right: &str) -> Self {
+    fn new(lang_left: &str, lang_right: &str, settings: &DatabaseSettings) -> Self {
+        let DatabaseSettings {
+            index_extra,
+            index_collapsed,
+            index_cased,
+            index_ngram,
+            locale_casing,
+            tokenizer,
+            ..
+        } = *settings;
         let mut schema_builder = Schema::builder();
-        let indexing_options = TEXT.set_indexing_options(
-            TextFieldIndexing::default()
-                .set_tokenizer("lowercase")
-                .set_index_option(IndexRecordOption::WithFreqsAndPositions),
-        ) | STORED;
+        let tokenizer_left = lowercase_tokenizer_name(lang_left);
+        let tokenizer_right = lowercase_tokenizer_name(lang_right);
+        let (lang_left_name, lang_right_name) = (lang_left, lang_right);
+        let indexing_options = |tokenizer: &str| {
+            TEXT.set_indexing_options(
+                TextFieldIndexing::default()
+                    .set_tokenizer(tokenizer)
+                    .set_index_option(IndexRecordOption::WithFreqsAndPositions),
+            ) | STORED
+        };
         let store_options = TextOptions::default()
             .set_indexing_options(TextFieldIndexing::default().set_tokenizer("lowercase"))
             | STORED;
 
-        let key_lang_left = schema_builder.add_text_field(&format!("key_{}", lang_left), indexing_options.clone());
-        let key_lang_right = schema_builder.add_text_field(&format!("key_{}", lang_right), indexing_options.clone());
-        let extra_lang_left = schema_builder.add_text_field(&format!("extra_{}", lang_left), indexing_options.clone());
-        let extra_lang_right = schema_builder.add_text_field(&format!("extra_{}", lang_right), indexing_options);
+        // New fields are appended to stay compatible with databases imported before
+        let frequency_lang_left_name = format!("frequency_{}", lang_left);
+        let frequency_lang_right_name = format!("frequency_{}", lang_right);
+        let collapsed_lang_left_name = collapsed_field_name(lang_left);
+        let collapsed_lang_right_name = collapsed_field_name(lang_right);
+        let cased_lang_left_name = cased_field_name(lang_left);
+        let cased_lang_right_name = cased_field_name(lang_right);
+        let ngram_lang_left_name = ngram_field_name(lang_left);
+        let ngram_lang_right_name = ngram_field_name(lang_right);
+        let exact_extra_lang_left_name = exact_extra_field_name(lang_left);
+        let exact_extra_lang_right_name = exact_extra_field_name(lang_right);
+
+        let key_lang_left =
+            schema_builder.add_text_field(&format!("key_{}", lang_left), indexing_options(&tokenizer_left));
+        let key_lang_right =
+            schema_builder.add_text_field(&format!("key_{}", lang_right), indexing_options(&tokenizer_right));
+        let extra_options = |tokenizer: &str| {
+            if index_extra {
+                indexing_options(tokenizer)
+            } else {
+                TextOptions::default()
+            }
+        };
+        let extra_lang_left =
+            schema_builder.add_text_field(&extra_field_name(lang_left), extra_options(&tokenizer_left));
+        let extra_lang_right =
+            schema_builder.add_text_field(&extra_field_name(lang_right), extra_options(&tokenizer_right));
         let lang_left = schema_builder.add_text_field(lang_left, store_options.clone());
         let lang_right = schema_builder.add_text_field(lang_right, store_options.clone());
         let word_classes = schema_builder.add_text_field("word_classes", store_options.clone());
         let subject_labels = schema_builder.add_text_field("subject_labels", store_options);
+        let frequency_lang_left = schema_builder.add_u64_field(&frequency_lang_left_name, STORED);
+        let frequency_lang_right = schema_builder.add_u64_field(&frequency_lang_right_name, STORED);
+        let source_file = schema_builder.add_text_field("source_file", STORED);
+        let collapsed_options = |tokenizer: &str| {
+            if index_collapsed {
+                TextOptions::default().set_indexing_options(TextFieldIndexing::default().set_tokenizer(tokenizer))
+            } else {
+                TextOptions::default()
+            }
+        };
+        let collapsed_lang_left =
+            schema_builder.add_text_field(&collapsed_lang_left_name, collapsed_options(&tokenizer_left));
+        let collapsed_lang_right =
+            schema_builder.add_text_field(&collapsed_lang_right_name, collapsed_options(&tokenizer_right));
+        let cased_options = if index_cased {
+            TextOptions::default().set_indexing_options(
+                TextFieldIndexing::default()
+                    .set_tokenizer("cased")
+                    .set_index_option(IndexRecordOption::WithFreqsAndPositions),
+            )
+        } else {
+            TextOptions::default()
+        };
+        let cased_lang_left = schema_builder.add_text_field(&cased_lang_left_name, cased_options.clone());
+        let cased_lang_right = schema_builder.add_text_field(&cased_lang_right_name, cased_options);
+        let ngram_options = |lang: &str| {
+            if index_ngram {
+                TextOptions::default()
+                    .set_indexing_options(TextFieldIndexing::default().set_tokenizer(&ngram_tokenizer_name(lang)))
+            } else {
+                TextOptions::default()
+            }
+        };
+        let ngram_lang_left = schema_builder.add_text_field(&ngram_lang_left_name, ngram_options(lang_left_name));
+        let ngram_lang_right = schema_builder.add_text_field(&ngram_lang_right_name, ngram_options(lang_right_name));
+        let exact_extra_options = if index_extra {
+            TextOptions::default().set_indexing_options(TextFieldIndexing::default().set_tokenizer("raw"))
+        } else {
+            TextOptions::default()
+        };
+        let exact_extra_lang_left =
+            schema_builder.add_text_field(&exact_extra_lang_left_name, exact_extra_options.clone());
+        let exact_extra_lang_right = schema_builder.add_text_field(&exact_extra_lang_right_name, exact_extra_options);
         let schema = schema_builder.build();
 
         let lowercase_tokenizer = TextAnalyzer::from(SimpleTokenizer)
             .filter(RemoveLongFilter::limit(tantivy::tokenizer::MAX_TOKEN_LEN))
             .filter(LowerCaser);
+        // Databases imported before were lowercased without the rules of the languages
+        let language_tokenizer = |tokenizer: TokenizerKind, lang: &str| {
+            let text_analyzer = tokenizer
+                .text_analyzer()
+                .filter(RemoveLongFilter::limit(tantivy::tokenizer::MAX_TOKEN_LEN));
+            if locale_casing {
+                text_analyzer.filter(LanguageLowerCaser {
+                    language: lang.to_owned(),
+                })
+            } else {
+                text_analyzer.filter(LowerCaser)
+            }
+        };
+        let lowercase_tokenizer_left = language_tokenizer(tokenizer, lang_left_name);
+        let lowercase_tokenizer_right = language_tokenizer(tokenizer, lang_right_name);
+        let ngram_tokenizer_left = language_tokenizer(TokenizerKind::Ngram, lang_left_name);
+        let ngram_tokenizer_right = language_tokenizer(TokenizerKind::Ngram, lang_right_name);
+        let cased_tokenizer = tokenizer
+            .text_analyzer()
+            .filter(RemoveLongFilter::limit(tantivy::tokenizer::MAX_TOKEN_LEN));
 
         Self {
             schema,
             lowercase_tokenizer,
+            lowercase_tokenizer_left,
+            lowercase_tokenizer_right,
+            cased_tokenizer,
+            ngram_tokenizer_left,
+            ngram_tokenizer_right,
             key_lang_left,
             key_lang_right,
             extra_lang_left,
@@ -64,14 +226,213 @@ impl DatabaseSchema {
             lang_right,
             word_classes,
             subject_labels,
+            frequency_lang_left,
+            frequency_lang_right,
+            source_file,
+            collapsed_lang_left,
+            collapsed_lang_right,
+            cased_lang_left,
+            cased_lang_right,
+            ngram_lang_left,
+            ngram_lang_right,
+            exact_extra_lang_left,
+            exact_extra_lang_right,
+            index_extra,
+            index_collapsed,
+            index_cased,
+            index_ngram,
+            tokenizer,
+        }
+    }
+}
+
+impl DatabaseSchema {
+    pub(crate) fn field_name(&self, field: Field) -> &str {
+        self.schema.get_field_name(field)
+    }
+
+    /// Registers the tokenizers that the fields are indexed with
+    fn register_tokenizers(&self, index: &Index) {
+        let tokenizers = index.tokenizers();
+        tokenizers.register("lowercase", self.lowercase_tokenizer.clone());
+        tokenizers.register(
+            &lowercase_tokenizer_name(self.field_name(self.lang_left)),
+            self.lowercase_tokenizer_left.clone(),
+        );
+        tokenizers.register(
+            &lowercase_tokenizer_name(self.field_name(self.lang_right)),
+            self.lowercase_tokenizer_right.clone(),
+        );
+        tokenizers.register("cased", self.cased_tokenizer.clone());
+        tokenizers.register(
+            &ngram_tokenizer_name(self.field_name(self.lang_left)),
+            self.ngram_tokenizer_left.clone(),
+        );
+        tokenizers.register(
+            &ngram_tokenizer_name(self.field_name(self.lang_right)),
+            self.ngram_tokenizer_right.clone(),
+        );
+    }
+
+    /// Matches the words exactly and adjacent.
+    /// N-grams have no positions, so every n-gram of the words has to match instead.
+    fn exact_query(&self, field: Field, words: &[String]) -> Box<dyn Query> {
+        let mut terms: Vec<Term> = words.iter().map(|word| Term::from_field_text(field, word)).collect();
+        if terms.len() == 1 {
+            Box::new(TermQuery::new(terms.pop().unwrap(), IndexRecordOption::Basic))
+        } else if !self.tokenizer.has_positions() {
+            all_terms_query(terms)
+        } else {
+            Box::new(PhraseQuery::new(terms))
+        }
+    }
+
+    /// The query with the field names, like `(+key_de:haus~1 -key_de:maus)` with `+` for required and `-` for
+    /// excluded parts, `~` for the fuzzy distance and slashes around regexes
+    fn describe_query(&self, query: &dyn Query) -> String {
+        let describe_term = |term: &Term| {
+            format!(
+                "{}:{}",
+                self.field_name(term.field()),
+                term.as_str().unwrap_or_default()
+            )
+        };
+        if let Some(boolean_query) = query.downcast_ref::<BooleanQuery>() {
+            let clauses = boolean_query
+                .clauses()
+                .iter()
+                .map(|(occur, query)| {
+                    let prefix = match occur {
+                        Occur::Must => "+",
+                        Occur::MustNot => "-",
+                        Occur::Should => "",
+                    };
+                    format!("{}{}", prefix, self.describe_query(query.as_ref()))
+                })
+                .join(" ");
+            format!("({})", clauses)
+        } else if let Some(term_query) = query.downcast_ref::<TermQuery>() {
+            describe_term(term_query.term())
+        } else if let Some(phrase_query) = query.downcast_ref::<PhraseQuery>() {
+            let words = phrase_query
+                .phrase_terms()
+                .iter()
+                .map(|term| term.as_str().unwrap_or_default().to_owned())
+                .join(" ");
+            format!("{}:\"{}\"", self.field_name(phrase_query.field()), words)
+        } else if let Some(described_query) = query.downcast_ref::<DescribedQuery>() {
+            described_query.description.clone()
+        } else {
+            format!("{:?}", query).lines().next().unwrap_or_default().to_owned()
+        }
+    }
+
+    /// The first field whose name differs from the stored schema of the index, e.g. after renaming the directory.
+    /// Fields appended after the database was imported may be missing, but not the fields of the first version.
+    fn mismatched_field(&self, index_schema: &Schema) -> Option<String> {
+        let required_fields = self.subject_labels.field_id() as usize + 1;
+        let index_fields = index_schema.fields().count().max(required_fields);
+        self.schema
+            .fields()
+            .take(index_fields)
+            .find(|(field, field_entry)| {
+                index_schema
+                    .fields()
+                    .nth(field.field_id() as usize)
+                    .map_or(true, |(_, index_field_entry)| {
+                        index_field_entry.name() != field_entry.name()
+                    })
+            })
+            .map(|(_, field_entry)| field_entry.name().to_owned())
+    }
+}
+
+fn extra_field_name(lang: &str) -> String {
+    format!("extra_{}", lang)
+}
+
+fn exact_extra_field_name(lang: &str) -> String {
+    format!("extra_exact_{}", lang)
+}
+
+fn lowercase_tokenizer_name(lang: &str) -> String {
+    format!("lowercase_{}", lang)
+}
+
+fn cased_field_name(lang: &str) -> String {
+    format!("cased_{}", lang)
+}
+
+fn collapsed_field_name(lang: &str) -> String {
+    format!("collapsed_{}", lang)
+}
+
+/// A query with its description for `--print-query`, as fuzzy and regex queries have no accessors for their term
+/// and regex
+#[derive(Debug)]
+struct DescribedQuery {
+    query: Box<dyn Query>,
+    description: String,
+}
+
+impl Clone for DescribedQuery {
+    fn clone(&self) -> Self {
+        DescribedQuery {
+            query: self.query.box_clone(),
+            description: self.description.clone(),
         }
     }
 }
 
-fn data_dir() -> Result<PathBuf, DictCliError> {
-    let data_dir = dirs::data_local_dir()
-        .ok_or(DictCliError::NoDataDirectory)?
-        .join("dictcc-cli");
+impl Query for DescribedQuery {
+    fn weight(&self, searcher: &Searcher, scoring_enabled: bool) -> tantivy::Result<Box<dyn Weight>> {
+        self.query.weight(searcher, scoring_enabled)
+    }
+
+    fn query_terms(&self, term_set: &mut BTreeMap<Term, bool>) {
+        self.query.query_terms(term_set)
+    }
+}
+
+fn ngram_field_name(lang: &str) -> String {
+    format!("ngram_{}", lang)
+}
+
+fn ngram_tokenizer_name(lang: &str) -> String {
+    format!("ngram_{}", lang)
+}
+
+/// The text without whitespace and punctuation, so that compounds and their spaced spellings are equal
+fn collapsed_text(text: &str) -> String {
+    text.chars().filter(|char| char.is_alphanumeric()).collect()
+}
+
+static CURRENT_DIR_WARNED: AtomicBool = AtomicBool::new(false);
+
+/// `DICTCC_DATA_DIR` if set, otherwise `dictcc-cli` in the data directory of the user.
+/// Without one, e.g. in containers, in `~/.local/share` or at last in the current directory.
+pub(crate) fn data_dir() -> Result<PathBuf, DictCliError> {
+    let data_dir = match std::env::var_os("DICTCC_DATA_DIR").filter(|dir| !dir.is_empty()) {
+        Some(data_dir) => PathBuf::from(data_dir),
+        None => match dirs::data_local_dir() {
+            Some(data_local_dir) => data_local_dir.join("dictcc-cli"),
+            None => match std::env::var_os("HOME").filter(|home| !home.is_empty()) {
+                Some(home) => Path::new(&home).join(".local/share/dictcc-cli"),
+                None => {
+                    let data_dir = std::env::current_dir()
+                        .map_err(|_| DictCliError::NoDataDirectory)?
+                        .join("dictcc-cli");
+                    if !CURRENT_DIR_WARNED.swap(true, Ordering::Relaxed) {
+                        eprintln!(
+                            "No data directory could be found, using {}. Set DICTCC_DATA_DIR to choose one.",
+                            data_dir.display()
+                        );
+                    }
+                    data_dir
+                }
+            },
+        },
+    };
     std::fs::create_dir_all(&data_dir)?;
     Ok(data_dir)
 }
@@ -80,14 +441,89 @@ fn lang_db_dir(lang_pair: &str) -> Result<PathBuf, DictCliError> {
     Ok(data_dir()?.join(normalized_lang_pair(lang_pair)?))
 }
 
-fn read_lang_pair<P: AsRef<Path>>(dictcc_path: P) -> Result<String, DictCliError> {
+/// The layout of an imported file
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ImportFormat {
+    /// The full translation file, starting with a comment like `# DE-EN vocabulary database`
+    Dictcc,
+    /// A user vocabulary list, starting with a header line of the two languages like `DE<tab>EN`.
+    /// Each line has the two words, further columns like comments are left out.
+    Vocab,
+}
+
+impl ImportFormat {
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            ImportFormat::Dictcc => "dictcc",
+            ImportFormat::Vocab => "vocab",
+        }
+    }
+
+    fn field_len(&self) -> usize {
+        match self {
+            ImportFormat::Dictcc => 4,
+            ImportFormat::Vocab => MIN_FIELD_LEN,
+        }
+    }
+}
+
+/// How the entries are split into tokens at import
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TokenizerKind {
+    /// Words, separated by whitespace and punctuation
+    Simple,
+    /// All parts of two or three characters, so that a search finds the entries containing it, e.g. `aus` finds `Haus`
+    Ngram,
+    /// Words, separated by whitespace only, so that e.g. `don't` or `e-mail` are kept as one word
+    Whitespace,
+}
+
+impl TokenizerKind {
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            TokenizerKind::Simple => "simple",
+            TokenizerKind::Ngram => "ngram",
+            TokenizerKind::Whitespace => "whitespace",
+        }
+    }
+
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "simple" => Some(TokenizerKind::Simple),
+            "ngram" => Some(TokenizerKind::Ngram),
+            "whitespace" => Some(TokenizerKind::Whitespace),
+            _ => None,
+        }
+    }
+
+    fn text_analyzer(&self) -> TextAnalyzer {
+        match self {
+            TokenizerKind::Simple => TextAnalyzer::from(SimpleTokenizer),
+            TokenizerKind::Ngram => TextAnalyzer::from(NgramTokenizer::all_ngrams(2, 3)),
+            TokenizerKind::Whitespace => TextAnalyzer::from(WhitespaceTokenizer),
+        }
+    }
+
+    /// Whether the tokens have positions, for phrase searches
+    fn has_positions(&self) -> bool {
+        !matches!(self, TokenizerKind::Ngram)
+    }
+}
+
+/// Detects the format and the language pair by the first line of the file
+fn read_header<P: AsRef<Path>>(dictcc_path: P, delimiter: u8) -> Result<(ImportFormat, String), DictCliError> {
     let file = OpenOptions::new().read(true).open(&dictcc_path)?;
     let mut buf = BufReader::new(file);
-    let mut first_line = String::with_capacity(100);
-    buf.read_line(&mut first_line)?;
-    let lang_pair = first_line
-        .strip_prefix('#')
-        .ok_or(DictCliError::NoLanguagePair)?
+    let mut first_line = Vec::with_capacity(100);
+    buf.read_until(b'\n', &mut first_line)?;
+    let first_line = String::from_utf8_lossy(&first_line);
+    let first_line = first_line.trim_start_matches('\u{feff}');
+
+    let comment = match first_line.strip_prefix('#') {
+        Some(comment) => comment,
+        None => return Ok((ImportFormat::Vocab, vocab_lang_pair(first_line, delimiter)?)),
+    };
+    let lang_pair = comment
         .split_whitespace()
         .next()
         .ok_or(DictCliError::NoLanguagePair)?
@@ -96,7 +532,20 @@ fn read_lang_pair<P: AsRef<Path>>(dictcc_path: P) -> Result<String, DictCliError
     if lang_pair.bytes().filter(|b| *b == b'-').count() != 1 {
         return Err(DictCliError::InvalidLanguagePair);
     }
-    Ok(lang_pair)
+    Ok((ImportFormat::Dictcc, lang_pair))
+}
+
+/// The language pair of a vocabulary list header like `DE<tab>EN`
+fn vocab_lang_pair(header: &str, delimiter: u8) -> Result<String, DictCliError> {
+    let is_language =
+        |column: &&str| (2..=3).contains(&column.len()) && column.chars().all(|c| c.is_ascii_alphabetic());
+    let mut columns = header.trim_end().split(char::from(delimiter)).map(str::trim);
+    match (columns.next(), columns.next()) {
+        (Some(lang_left), Some(lang_right)) if is_language(&lang_left) && is_language(&lang_right) => {
+            Ok(format!("{}-{}", lang_left, lang_right).to_lowercase())
+        }
+        _ => Err(DictCliError::NoLanguagePair),
+    }
 }
 
 pub(crate) fn languages(lang_pair: &str) -> Result<(&str, &str), DictCliError> {
@@ -126,7 +575,7 @@ pub(crate) fn available_language_pairs() -> Option<Box<[String]>> {
             let entry = entry.ok()?;
             if entry.file_type().ok()?.is_dir() {
                 let language_pair = entry.file_name().into_string().ok()?;
-                if language_pair.split('-').count() != 2 {
+                if language_pair.starts_with('.') || language_pair.split('-').count() != 2 {
                     return None;
                 }
                 return Some(language_pair);
@@ -151,15 +600,45 @@ pub(crate) fn available_languages(language_pairs: &[String]) -> Box<[String]> {
         .collect()
 }
 
-fn get_csv_reader_from_path<P: AsRef<Path>>(path: P) -> Result<csv::Reader<File>, DictCliError> {
-    Ok(csv::ReaderBuilder::new()
-        .delimiter(b'\t')
-        .has_headers(false)
+fn get_csv_reader_from_path<P: AsRef<Path>>(
+    path: P,
+    delimiter: u8,
+    format: ImportFormat,
+) -> Result<csv::Reader<File>, DictCliError> {
+    Ok(csv_reader(open_without_bom(path)?, delimiter, format))
+}
+
+/// Opens the file after its byte order mark, so that the first line is still recognized as comment
+fn open_without_bom<P: AsRef<Path>>(path: P) -> Result<File, DictCliError> {
+    const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+    let mut file = File::open(&path)?;
+    let mut start = Vec::with_capacity(UTF8_BOM.len());
+    (&mut file).take(UTF8_BOM.len() as u64).read_to_end(&mut start)?;
+    if start != UTF8_BOM {
+        file.seek(SeekFrom::Start(0))?;
+    }
+    Ok(file)
+}
+
+fn csv_reader<R: Read>(reader: R, delimiter: u8, format: ImportFormat) -> csv::Reader<R> {
+    csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(format == ImportFormat::Vocab)
         .quoting(false)
         .comment(Some(b'#'))
-        .from_path(&path)?)
+        .from_reader(reader)
+}
+
+fn decode_field(field: &[u8], lossy: bool) -> Result<Cow<'_, str>, Utf8Error> {
+    if lossy {
+        Ok(String::from_utf8_lossy(field))
+    } else {
+        std::str::from_utf8(field).map(Cow::Borrowed)
+    }
 }
 
+/// Checks whether the database may be imported, an existing one is only replaced after a successful import
 fn prepare_import<P: AsRef<Path>>(db_dir: P, force_import: bool) -> Result<(), DictCliError> {
     let path = db_dir.as_ref();
 
@@ -170,224 +649,1938 @@ fn prepare_import<P: AsRef<Path>>(db_dir: P, force_import: bool) -> Result<(), D
 
         if !force_import {
             return Err(DictCliError::AlreadyImported);
-        } else {
-            std::fs::remove_dir_all(path)?;
         }
     }
 
-    std::fs::create_dir_all(path)?;
-
     Ok(())
 }
 
-pub(crate) fn import_dictcc_file<P: AsRef<Path>>(dictcc_path: P, force_import: bool) -> Result<(), DictCliError> {
-    const FIELD_LEN: usize = 4;
-    const MIN_FIELD_LEN: usize = 2;
-    const DATABASE_WRITER_BUFFER_BYTES: usize = 10485760; // 10 MiB
-
-    let mut stdout_lock = stdout().lock();
-    writeln!(stdout_lock, "Initializing database...").unwrap();
+/// Directory next to the databases for an import in progress,
+/// it starts with a dot so that it is never taken for a language pair
+fn import_temp_dir(lang_pair: &str, purpose: &str) -> Result<PathBuf, DictCliError> {
+    Ok(data_dir()?.join(format!(".{}-{}", purpose, normalized_lang_pair(lang_pair)?)))
+}
 
-    let lang_pair = read_lang_pair(&dictcc_path)?;
-    let (lang_left, lang_right) = languages(&lang_pair)?;
-    let mut input_reader = get_csv_reader_from_path(&dictcc_path)?;
-    let db_directory = lang_db_dir(&lang_pair)?;
+/// Moves the new database to its place, the old one is removed afterwards
+fn replace_database(new_db_dir: &Path, db_dir: &Path, old_db_dir: &Path) -> Result<(), DictCliError> {
+    if db_dir.try_exists()? {
+        if old_db_dir.try_exists()? {
+            std::fs::remove_dir_all(old_db_dir)?;
+        }
+        std::fs::rename(db_dir, old_db_dir)?;
+        if let Err(err) = std::fs::rename(new_db_dir, db_dir) {
+            std::fs::rename(old_db_dir, db_dir)?;
+            return Err(err.into());
+        }
+        std::fs::remove_dir_all(old_db_dir)?;
+    } else {
+        std::fs::rename(new_db_dir, db_dir)?;
+    }
+    Ok(())
+}
 
-    // Indexing documents
-    // Here we use a buffer that will be split between indexing threads.
-    prepare_import(&db_directory, force_import)?;
-    let db_schema = DatabaseSchema::new(lang_left, lang_right);
-    let index = Index::create_in_dir(&db_directory, db_schema.schema.clone())?;
-    index.tokenizers().register("lowercase", db_schema.lowercase_tokenizer);
+pub(crate) struct ImportOptions {
+    pub(crate) force: bool,
+    pub(crate) delimiter: u8,
+    /// Replace invalid UTF-8 instead of skipping the line
+    pub(crate) lossy: bool,
+    pub(crate) frequency_files: Vec<PathBuf>,
+    /// Do not index the angle bracket annotations to save space
+    pub(crate) no_extra_index: bool,
+    /// Decode HTML entities like `&amp;`
+    pub(crate) html_decode: bool,
+    /// Also index the entries without spaces, see `DatabaseSchema::collapsed_lang_left`
+    pub(crate) index_collapsed: bool,
+    /// Also index the keys without lowercasing, see `DatabaseSchema::cased_lang_left`
+    pub(crate) index_cased: bool,
+    /// Also index the n-grams of the entries, see `DatabaseSchema::ngram_lang_left`
+    pub(crate) index_ngram: bool,
+    /// Skip entries with a longer source or target, in characters
+    pub(crate) max_entry_len: Option<usize>,
+    /// The expected format, detected by the first line otherwise
+    pub(crate) format: Option<ImportFormat>,
+    /// Skip entries with the same source and target as an earlier one, which costs memory for every entry
+    pub(crate) dedup: bool,
+    /// Do not read the file once before the import to count the entries for the progress
+    pub(crate) no_precount: bool,
+    /// Reuse the normalized entries of an earlier import of the same file, see `NormalizationCache`
+    pub(crate) normalization_cache: bool,
+    /// Print this many of the most common word classes and subject labels after the import
+    pub(crate) summary: Option<usize>,
+    pub(crate) tokenizer: TokenizerKind,
+}
 
-    let mut index_writer = index.writer(DATABASE_WRITER_BUFFER_BYTES)?;
+/// The options of an import without flags
+#[cfg(test)]
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self {
+            force: false,
+            delimiter: b'\t',
+            lossy: false,
+            frequency_files: Vec::new(),
+            no_extra_index: false,
+            html_decode: true,
+            index_collapsed: false,
+            index_cased: false,
+            index_ngram: false,
+            max_entry_len: None,
+            format: None,
+            dedup: false,
+            no_precount: false,
+            normalization_cache: false,
+            summary: None,
+            tokenizer: TokenizerKind::Simple,
+        }
+    }
+}
 
-    let current_pos = input_reader.position().clone();
-    let records_count = input_reader.records().count();
-    input_reader.seek(current_pos)?;
+/// Reads word frequency lists with one `word count` pair per line
+fn read_frequency_files(paths: &[PathBuf]) -> Result<HashMap<String, u64>, DictCliError> {
+    let mut frequencies = HashMap::new();
 
-    for (index, record) in input_reader.into_records().enumerate() {
-        write!(stdout_lock, "\r-> Processing {}/{}", index + 1, records_count).unwrap();
+    for path in paths {
+        let file = OpenOptions::new().read(true).open(path)?;
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            let line = line.trim();
 
-        let record = match record {
-            Ok(record) => record,
-            Err(err) => {
-                eprintln!("\n{}", err);
+            if line.is_empty() {
                 continue;
             }
-        };
 
-        let mut fields: Vec<String> = record
-            .into_iter()
-            .take(FIELD_LEN)
-            .map(|field| html_escape::decode_html_entities(field).nfc().collect())
-            .collect();
+            let (word, count) = match line.rsplit_once(char::is_whitespace) {
+                Some((word, count)) => match count.parse::<u64>() {
+                    Ok(count) => (word, count),
+                    Err(_) => match line.split_once(char::is_whitespace) {
+                        Some((count, word)) if count.parse::<u64>().is_ok() => (word, count.parse().unwrap()),
+                        _ => {
+                            eprintln!("Invalid line in frequency file: {}", line);
+                            continue;
+                        }
+                    },
+                },
+                None => {
+                    eprintln!("Invalid line in frequency file: {}", line);
+                    continue;
+                }
+            };
 
-        if fields.len() < MIN_FIELD_LEN {
-            continue;
+            let word: String = remove_multiple_whitespace(word).to_lowercase().nfc().collect();
+            *frequencies.entry(word).or_insert(0) += count;
         }
+    }
 
-        let field_lang_left = std::mem::take(&mut fields[0]);
-        let field_lang_right = std::mem::take(&mut fields[1]);
-        let field_word_classes = fields.get_mut(2).map(std::mem::take).unwrap_or_else(String::new);
-        let field_subject_labels = fields.get_mut(3).map(std::mem::take).unwrap_or_else(String::new);
+    Ok(frequencies)
+}
 
-        let normalized_left = match normalized_entry(&field_lang_left, true) {
-            Ok(result) => result,
-            Err(err) => {
-                eprintln!("\n{}", err);
-                continue;
-            }
-        };
+/// The frequency of the whole text, or of its rarest word if the text is not listed
+fn text_frequency(frequencies: &HashMap<String, u64>, text: &str) -> u64 {
+    let text = text.to_lowercase();
 
-        let normalized_right = match normalized_entry(&field_lang_right, true) {
-            Ok(result) => result,
-            Err(err) => {
-                eprintln!("\n{}", err);
-                continue;
-            }
-        };
+    if let Some(frequency) = frequencies.get(&text) {
+        return *frequency;
+    }
+
+    text.split_whitespace()
+        .map(|word| frequencies.get(word.trim_matches(|c| c == '(' || c == ')')).copied())
+        .min()
+        .flatten()
+        .unwrap_or(0)
+}
 
-        if index == records_count - 1 {
-            writeln!(stdout_lock).unwrap();
+/// Minimum number of fields of a record, the word classes and subject labels are optional
+const MIN_FIELD_LEN: usize = 2;
+
+/// The format and language pair of the header, which has to be of the format given with `--format`
+fn read_import_header<P: AsRef<Path>>(
+    dictcc_path: P,
+    options: &ImportOptions,
+) -> Result<(ImportFormat, String), DictCliError> {
+    let (format, lang_pair) = read_header(&dictcc_path, options.delimiter)?;
+    if let Some(expected_format) = options.format {
+        if expected_format != format {
+            return Err(DictCliError::ImportFormatMismatch(
+                expected_format.name(),
+                format.name(),
+            ));
         }
+    }
+    Ok((format, lang_pair))
+}
 
-        index_writer.add_document(doc!(
-            db_schema.key_lang_left => normalized_left.text,
-            db_schema.key_lang_right => normalized_right.text,
-            db_schema.extra_lang_left => normalized_left.extra,
-            db_schema.extra_lang_right => normalized_right.extra,
-            db_schema.lang_left => field_lang_left,
-            db_schema.lang_right => field_lang_right,
-            db_schema.word_classes => field_word_classes,
-            db_schema.subject_labels => field_subject_labels,
-        ))?;
+pub(crate) fn import_dictcc_file<P: AsRef<Path>>(
+    dictcc_path: P,
+    options: ImportOptions,
+) -> Result<ImportSummary, DictCliError> {
+    let mut stdout_lock = stdout().lock();
+    writeln!(stdout_lock, "Initializing database...").unwrap();
+
+    let (format, lang_pair) = read_import_header(&dictcc_path, &options)?;
+    let (lang_left, lang_right) = languages(&lang_pair)?;
+    let db_directory = lang_db_dir(&lang_pair)?;
+    let frequencies = read_frequency_files(&options.frequency_files)?;
+
+    // Ctrl-C only sets the flag instead of ending the process, so that the partial database is removed like after
+    // any other error. A second Ctrl-C ends the process right away, as the first one already set the flag when the
+    // shutdown handler checks it. The handlers stay registered until the process ends after the import.
+    let interrupted = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register_conditional_shutdown(
+        signal_hook::consts::SIGINT,
+        DictCliError::ImportInterrupted.exit_code(),
+        Arc::clone(&interrupted),
+    )?;
+    signal_hook::flag::register(signal_hook::consts::SIGINT, Arc::clone(&interrupted))?;
+
+    let empty_dictionary = || DictCliError::EmptyDictionary(dictcc_path.as_ref().to_string_lossy().into_owned());
+    // The key of the normalization cache is hashed while counting, so that the file is not read once more for it
+    let mut cache_key_hasher = options
+        .normalization_cache
+        .then(|| normalization_cache_hasher(&options, format));
+    let records_count = if options.no_precount {
+        None
+    } else {
+        let file = HashingReader {
+            inner: open_without_bom(&dictcc_path)?,
+            hasher: cache_key_hasher.as_mut(),
+        };
+        let mut reader = csv_reader(file, options.delimiter, format);
+        let mut record = ByteRecord::new();
+        let mut records_count = 0;
+        while reader.read_byte_record(&mut record).unwrap_or(true) {
+            if interrupted.load(Ordering::Relaxed) {
+                return Err(DictCliError::ImportInterrupted);
+            }
+            records_count += 1;
+        }
+        Some(records_count)
+    };
+
+    if records_count == Some(0) {
+        return Err(empty_dictionary());
     }
 
-    // We need to call .commit() explicitly to force the
-    // index_writer to finish processing the documents in the queue,
-    // flush the current index to the disk, and advertise
-    // the existence of new documents.
-    index_writer.commit()?;
+    prepare_import(&db_directory, options.force)?;
+    let settings = DatabaseSettings::from_import_options(&options);
+    let db_schema = DatabaseSchema::new(lang_left, lang_right, &settings);
+    let index_extra = db_schema.index_extra;
+
+    // The new database is built next to the old one, which stays intact if the import fails
+    let new_db_directory = import_temp_dir(&lang_pair, "import")?;
+    if new_db_directory.try_exists()? {
+        // Left by an import that failed, unless another import of the language pair is still writing it
+        if let Ok(index) = Index::open_in_dir(&new_db_directory) {
+            drop(acquire_writer(&index)?);
+        }
+        std::fs::remove_dir_all(&new_db_directory)?;
+    }
+    std::fs::create_dir_all(&new_db_directory)?;
+
+    let normalization_cache = match cache_key_hasher {
+        Some(mut hasher) => {
+            if options.no_precount {
+                let mut file = HashingReader {
+                    inner: open_without_bom(&dictcc_path)?,
+                    hasher: Some(&mut hasher),
+                };
+                std::io::copy(&mut file, &mut std::io::sink())?;
+            }
+            Some(NormalizationCache::open(&lang_pair, hasher.finish())?)
+        }
+        None => None,
+    };
+
+    let written = Index::create_in_dir(&new_db_directory, db_schema.schema.clone())
+        .map_err(DictCliError::from)
+        .and_then(|index| {
+            let input = ImportInput {
+                reader: get_csv_reader_from_path(&dictcc_path, options.delimiter, format)?,
+                source_file: dictcc_path
+                    .as_ref()
+                    .file_name()
+                    .map(|file_name| file_name.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                records_count,
+                format,
+                normalization_cache,
+                interrupted: Arc::clone(&interrupted),
+            };
+            write_database(&index, db_schema, input, &frequencies, &options, &mut stdout_lock)
+        })
+        .and_then(|summary| {
+            // Without the count beforehand an empty file is only noticed after reading it
+            if summary.records == 0 {
+                return Err(empty_dictionary());
+            }
+            settings.write(&new_db_directory)?;
+            // Also a Ctrl-C while committing keeps the old database
+            if interrupted.load(Ordering::Relaxed) {
+                return Err(DictCliError::ImportInterrupted);
+            }
+            replace_database(&new_db_directory, &db_directory, &import_temp_dir(&lang_pair, "old")?)?;
+            Ok(summary)
+        });
+
+    let summary = match written {
+        Ok(summary) => summary,
+        Err(err) => {
+            // The partial database is of no use and only takes up the space, the same goes for a partial cache
+            let _ = std::fs::remove_dir_all(&new_db_directory);
+            if let Ok(new_cache_path) = import_temp_dir(&lang_pair, "normalized-new") {
+                let _ = std::fs::remove_file(new_cache_path);
+            }
+            if is_disk_full(&err) {
+                return Err(DictCliError::DiskFull(new_db_directory.to_string_lossy().into_owned()));
+            }
+            return Err(err);
+        }
+    };
 
     writeln!(stdout_lock, "Initialized database.").unwrap();
 
-    Ok(())
+    if !index_extra {
+        writeln!(
+            stdout_lock,
+            "Database size {}, skipped indexing {} of annotations.",
+            crate::format_bytes(directory_size(&db_directory)?),
+            crate::format_bytes(summary.skipped_extra_bytes as u64)
+        )
+        .unwrap();
+    }
+
+    if let Some(max_entry_len) = options.max_entry_len {
+        writeln!(
+            stdout_lock,
+            "Skipped {} entries longer than {} characters.",
+            summary.too_long_records, max_entry_len
+        )
+        .unwrap();
+    }
+
+    if options.dedup {
+        writeln!(stdout_lock, "Skipped {} duplicate entries.", summary.duplicate_records).unwrap();
+    }
+
+    // Most likely the whole line ended up in one field
+    if summary.too_short_records > 0 && summary.too_short_records * 2 >= summary.records {
+        eprintln!(
+            "Warning: {} of {} lines had less than {} columns and were skipped. \
+            The file might not be tab-separated, try --delimiter.",
+            summary.too_short_records, summary.records, MIN_FIELD_LEN
+        );
+    }
+
+    Ok(summary)
 }
 
-pub(crate) fn remove_database(lang_pair: &str) -> Result<(), DictCliError> {
-    std::fs::remove_dir_all(lang_db_dir(lang_pair)?)?;
-    Ok(())
+pub(crate) struct ImportSummary {
+    /// The records read from the file, including the skipped ones
+    records: usize,
+    too_short_records: usize,
+    too_long_records: usize,
+    duplicate_records: usize,
+    skipped_extra_bytes: usize,
+    /// How many entries have each word class and subject label, counted with `--summary`
+    pub(crate) word_classes: HashMap<String, usize>,
+    pub(crate) subject_labels: HashMap<String, usize>,
 }
 
-pub(crate) struct DatabaseSearch {
-    pub(crate) schema: DatabaseSchema,
-    reader: IndexReader,
-    lang_left: String,
-    lang_right: String,
+/// The labels of a subject labels field like `[med.] [pharm.]`, without the brackets
+pub(crate) fn split_subject_labels(subject_labels: &str) -> impl Iterator<Item = &str> {
+    subject_labels
+        .split(|c: char| c == '[' || c == ']' || c.is_whitespace())
+        .filter(|subject_label| !subject_label.is_empty())
 }
 
-impl DatabaseSearch {
-    pub(crate) fn new(lang_pair: &str) -> Result<Self, DictCliError> {
-        let db_dir = lang_db_dir(lang_pair)?;
-        let index = Index::open_in_dir(&db_dir)?;
-        let reader = index.reader()?;
-        let normalized_lang_pair = normalized_lang_pair(lang_pair)?;
-        let (lang_left, lang_right) = languages(&normalized_lang_pair)?;
-        let schema = DatabaseSchema::new(lang_left, lang_right);
-        Ok(Self {
-            schema,
-            reader,
-            lang_left: lang_left.to_owned(),
-            lang_right: lang_right.to_owned(),
-        })
+/// Opens the index writer, waiting with increasing delays while another process holds it
+fn acquire_writer(index: &Index) -> Result<IndexWriter, DictCliError> {
+    // Here we use a buffer that will be split between indexing threads.
+    const DATABASE_WRITER_BUFFER_BYTES: usize = 10485760; // 10 MiB
+    const LOCK_RETRIES: u32 = 6;
+
+    let mut delay = Duration::from_millis(100);
+    for _ in 0..LOCK_RETRIES {
+        match index.writer(DATABASE_WRITER_BUFFER_BYTES) {
+            Err(TantivyError::LockFailure(LockError::LockBusy, _)) => {
+                std::thread::sleep(delay);
+                delay *= 2;
+            }
+            result => return Ok(result?),
+        }
+    }
+    match index.writer(DATABASE_WRITER_BUFFER_BYTES) {
+        Err(TantivyError::LockFailure(LockError::LockBusy, _)) => Err(DictCliError::DatabaseLocked),
+        result => Ok(result?),
     }
+}
 
-    pub(crate) fn is_reverse_langs(&self, language_from: &str) -> Result<bool, DictCliError> {
-        if language_from == self.lang_left {
-            Ok(false)
-        } else if language_from == self.lang_right {
-            Ok(true)
-        } else {
-            Err(DictCliError::SearchLanguageNotAvailable(
-                language_from.to_owned(),
-                format!("{}, {}", self.lang_left, self.lang_right),
-            ))
-        }
+/// The records of a dict.cc file to import
+struct ImportInput<R: Read> {
+    reader: csv::Reader<R>,
+    /// Recorded with every entry, see `--show-source`
+    source_file: String,
+    /// Shown with the progress, unknown with `--no-precount`
+    records_count: Option<usize>,
+    format: ImportFormat,
+    normalization_cache: Option<NormalizationCache>,
+    /// Set by Ctrl-C, the import stops before the next record
+    interrupted: Arc<AtomicBool>,
+}
+
+/// Bumped whenever `normalized_entry` changes, so that the entries of older versions are normalized again
+const NORMALIZATION_CACHE_VERSION: u32 = 3;
+
+/// The 64 bit FNV-1a hash, which unlike `DefaultHasher` stays the same between builds and Rust versions
+struct Fnv1aHasher(u64);
+
+impl Default for Fnv1aHasher {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
     }
+}
 
-    pub(crate) fn target_language(&self, language_from: &str) -> Result<&str, DictCliError> {
-        if language_from == self.lang_left {
-            Ok(&self.lang_right)
-        } else if language_from == self.lang_right {
-            Ok(&self.lang_left)
-        } else {
-            Err(DictCliError::SearchLanguageNotAvailable(
-                language_from.to_owned(),
-                format!("{}, {}", self.lang_left, self.lang_right),
-            ))
-        }
+impl Hasher for Fnv1aHasher {
+    fn finish(&self) -> u64 {
+        self.0
     }
 
-    fn tokenize_search_expression(&self, expression: &str) -> Vec<String> {
-        let a = &self.schema.lowercase_tokenizer;
-        let mut token_stream = a.token_stream(expression);
-        let mut tokens: Vec<String> = Vec::with_capacity(32);
-        while token_stream.advance() {
-            tokens.push(std::mem::take(&mut token_stream.token_mut().text));
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3);
         }
-        tokens
     }
+}
 
-    pub(crate) fn search_database(
-        &self,
-        reverse_langs: bool,
-        expression: &str,
-        fuzzy_distance: u8,
-    ) -> Result<Vec<Document>, DictCliError> {
-        if expression.trim().is_empty() {
-            return Ok(Vec::new());
+/// Hashes what is read, e.g. to identify a file while it is read anyway
+struct HashingReader<'a, R: Read> {
+    inner: R,
+    hasher: Option<&'a mut Fnv1aHasher>,
+}
+
+impl<R: Read> Read for HashingReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        if let Some(hasher) = self.hasher.as_mut() {
+            hasher.write(&buf[..len]);
         }
+        Ok(len)
+    }
+}
 
-        let searcher = self.reader.searcher();
-        let (key_field, extra_field) = if !reverse_langs {
-            (self.schema.key_lang_left, self.schema.extra_lang_left)
-        } else {
-            (self.schema.key_lang_right, self.schema.extra_lang_right)
-        };
+/// Starts the key of the normalization cache with the options that change the normalized entries,
+/// the content of the file is hashed after them
+fn normalization_cache_hasher(options: &ImportOptions, format: ImportFormat) -> Fnv1aHasher {
+    let mut hasher = Fnv1aHasher::default();
+    hasher.write(&NORMALIZATION_CACHE_VERSION.to_le_bytes());
+    hasher.write(&[
+        options.delimiter,
+        u8::from(options.lossy),
+        u8::from(options.html_decode),
+    ]);
+    hasher.write(format.name().as_bytes());
+    hasher.write(&[0]);
+    hasher
+}
 
-        let mut fuzzy_queries: Vec<(Occur, Box<dyn Query>)> = Vec::with_capacity(32);
-        let mut extra_terms: Vec<Term> = Vec::with_capacity(32);
-        for word in self.tokenize_search_expression(&expression.nfc().collect::<String>()) {
-            extra_terms.push(Term::from_field_text(extra_field, &word));
-            let term = Term::from_field_text(key_field, &word);
-            let query = FuzzyTermQuery::new(term, fuzzy_distance, true);
-            fuzzy_queries.push((Occur::Must, Box::new(query)));
+/// Escapes the separators of the lines and fields of the normalization cache
+fn escape_cache_field(field: &str) -> String {
+    field
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+fn unescape_cache_field(field: &str) -> String {
+    let mut unescaped = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => unescaped.push('\t'),
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some(c) => unescaped.push(c),
+            None => {}
+        }
+    }
+    unescaped
+}
+
+/// The normalized source and target of every record of an import, so that importing the same file again, e.g.
+/// with other options, does not parse every entry again. It is one file per language pair next to the databases,
+/// with the key of the imported file in the first line and one line per normalized record after it. The fields of a
+/// line are separated by tabs and escaped with `escape_cache_field`, the annotations are preceded by their number.
+enum NormalizationCache {
+    /// The entries of an earlier import with the same key, with the next line that was read ahead
+    Read {
+        lines: Lines<BufReader<File>>,
+        next_line: Option<(usize, String)>,
+    },
+    /// Collects the entries of this import, it replaces the old cache once all records are read
+    Write {
+        writer: BufWriter<File>,
+        new_path: PathBuf,
+        path: PathBuf,
+    },
+}
+
+impl NormalizationCache {
+    fn open(lang_pair: &str, key: u64) -> Result<Self, DictCliError> {
+        let path = import_temp_dir(lang_pair, "normalized")?;
+        let new_path = import_temp_dir(lang_pair, "normalized-new")?;
+        Self::open_path(path, new_path, key)
+    }
+
+    /// Reads the cache at the path if it has the key, otherwise writes a new one that replaces it when finished
+    fn open_path(path: PathBuf, new_path: PathBuf, key: u64) -> Result<Self, DictCliError> {
+        let key = format!("{:016x}", key);
+
+        match File::open(&path) {
+            Ok(file) => {
+                let mut lines = BufReader::new(file).lines();
+                if lines.next().transpose()?.as_deref() == Some(key.as_str()) {
+                    let mut cache = NormalizationCache::Read { lines, next_line: None };
+                    cache.read_line()?;
+                    return Ok(cache);
+                }
+            }
+            Err(err) if err.kind() == ErrorKind::NotFound => {}
+            Err(err) => return Err(err.into()),
+        }
+
+        let mut writer = BufWriter::new(File::create(&new_path)?);
+        writeln!(writer, "{}", key)?;
+        Ok(NormalizationCache::Write { writer, new_path, path })
+    }
+
+    fn read_line(&mut self) -> Result<(), DictCliError> {
+        if let NormalizationCache::Read { lines, next_line } = self {
+            *next_line = match lines.next().transpose()? {
+                Some(line) => line
+                    .split_once('\t')
+                    .and_then(|(record, entries)| Some((record.parse().ok()?, entries.to_owned()))),
+                None => None,
+            };
+        }
+        Ok(())
+    }
+
+    /// The normalized source and target of the record with the given number, if the earlier import had them.
+    /// The lines are ordered by their record, so the lines of skipped records are passed over.
+    fn get(&mut self, record: usize) -> Result<Option<(NormalizedEntry, NormalizedEntry)>, DictCliError> {
+        loop {
+            let entries = match self {
+                NormalizationCache::Read {
+                    next_line: Some((line_record, entries)),
+                    ..
+                } if *line_record <= record => (*line_record == record).then(|| std::mem::take(entries)),
+                _ => return Ok(None),
+            };
+            self.read_line()?;
+            if let Some(entries) = entries {
+                let mut parts = entries.split('\t').map(unescape_cache_field);
+                let mut next_entry = || {
+                    let text = parts.next()?;
+                    let extra = parts.next()?;
+                    let annotations_count = parts.next()?.parse().ok()?;
+                    let annotations = parts.by_ref().take(annotations_count).collect::<Vec<_>>();
+                    Some(NormalizedEntry {
+                        text,
+                        extra,
+                        annotations: (annotations.len() == annotations_count).then_some(annotations)?,
+                        phonetic: parts.next()?,
+                    })
+                };
+                return Ok(next_entry().zip(next_entry()));
+            }
+        }
+    }
+
+    fn put(&mut self, record: usize, left: &NormalizedEntry, right: &NormalizedEntry) -> Result<(), DictCliError> {
+        if let NormalizationCache::Write { writer, .. } = self {
+            write!(writer, "{}", record)?;
+            for entry in [left, right] {
+                write!(
+                    writer,
+                    "\t{}\t{}\t{}",
+                    escape_cache_field(&entry.text),
+                    escape_cache_field(&entry.extra),
+                    entry.annotations.len()
+                )?;
+                for annotation in &entry.annotations {
+                    write!(writer, "\t{}", escape_cache_field(annotation))?;
+                }
+                write!(writer, "\t{}", escape_cache_field(&entry.phonetic))?;
+            }
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+
+    /// Moves the written cache in place of the old one
+    fn finish(self) -> Result<(), DictCliError> {
+        if let NormalizationCache::Write {
+            mut writer,
+            new_path,
+            path,
+        } = self
+        {
+            writer.flush()?;
+            drop(writer);
+            std::fs::rename(new_path, path)?;
+        }
+        Ok(())
+    }
+}
+
+/// Indexes the records of the dict.cc file into the index, which is on disk or in memory
+fn write_database<R: Read>(
+    index: &Index,
+    db_schema: DatabaseSchema,
+    input: ImportInput<R>,
+    frequencies: &HashMap<String, u64>,
+    options: &ImportOptions,
+    progress: &mut impl Write,
+) -> Result<ImportSummary, DictCliError> {
+    // Indexing documents
+    db_schema.register_tokenizers(index);
+
+    let mut index_writer = acquire_writer(index)?;
+    let ImportInput {
+        reader: mut input_reader,
+        source_file,
+        records_count,
+        format,
+        mut normalization_cache,
+        interrupted,
+    } = input;
+
+    let mut records = 0;
+    let mut too_short_records = 0;
+    let mut too_long_records = 0;
+    let mut duplicate_records = 0;
+    let mut skipped_extra_bytes = 0;
+    let mut word_classes: HashMap<String, usize> = HashMap::new();
+    let mut subject_labels: HashMap<String, usize> = HashMap::new();
+    // Hashes of the source and target of the entries, instead of the entries themselves to save memory
+    let mut seen_entries: HashSet<u64> = HashSet::new();
+
+    // One record is read at a time into the same buffer, so that the memory does not grow with the file
+    let mut record = ByteRecord::new();
+    loop {
+        if interrupted.load(Ordering::Relaxed) {
+            writeln!(progress).unwrap();
+            return Err(DictCliError::ImportInterrupted);
+        }
+
+        match input_reader.read_byte_record(&mut record) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(err) => {
+                eprintln!("\n{}", err);
+                continue;
+            }
+        }
+
+        records += 1;
+        match records_count {
+            Some(records_count) => write!(progress, "\r-> Processing {}/{}", records, records_count).unwrap(),
+            None => write!(progress, "\r-> Processing {}", records).unwrap(),
+        }
+
+        let fields: Result<Vec<String>, Utf8Error> = record
+            .iter()
+            .take(format.field_len())
+            .map(|field| {
+                decode_field(field, options.lossy).map(|field| {
+                    if options.html_decode {
+                        html_escape::decode_html_entities(&field).nfc().collect()
+                    } else {
+                        field.nfc().collect()
+                    }
+                })
+            })
+            .collect();
+
+        let mut fields = match fields {
+            Ok(fields) => fields,
+            Err(err) => {
+                eprintln!(
+                    "\nInvalid UTF-8 in line {}: {}. Use --lossy to replace invalid characters.",
+                    record.position().map_or(0, |position| position.line()),
+                    err
+                );
+                continue;
+            }
+        };
+
+        if fields.len() < MIN_FIELD_LEN {
+            too_short_records += 1;
+            continue;
+        }
+
+        if let Some(max_entry_len) = options.max_entry_len {
+            if fields[..MIN_FIELD_LEN]
+                .iter()
+                .any(|field| field.chars().count() > max_entry_len)
+            {
+                too_long_records += 1;
+                continue;
+            }
+        }
+
+        if options.dedup {
+            let mut hasher = DefaultHasher::new();
+            (&fields[0], &fields[1]).hash(&mut hasher);
+            if !seen_entries.insert(hasher.finish()) {
+                duplicate_records += 1;
+                continue;
+            }
+        }
+
+        let field_lang_left = std::mem::take(&mut fields[0]);
+        let field_lang_right = std::mem::take(&mut fields[1]);
+        let field_word_classes = fields.get_mut(2).map(std::mem::take).unwrap_or_default();
+        let field_subject_labels = fields.get_mut(3).map(std::mem::take).unwrap_or_default();
+
+        if options.summary.is_some() {
+            for word_class in field_word_classes.split_whitespace() {
+                *word_classes.entry(word_class.to_owned()).or_default() += 1;
+            }
+            for subject_label in split_subject_labels(&field_subject_labels) {
+                *subject_labels.entry(subject_label.to_owned()).or_default() += 1;
+            }
+        }
+
+        let cached = match normalization_cache.as_mut() {
+            Some(normalization_cache) => normalization_cache.get(records)?,
+            None => None,
+        };
+        let (normalized_left, normalized_right) = match cached {
+            Some(normalized) => normalized,
+            None => {
+                let normalized_left = match normalized_entry(&field_lang_left, true) {
+                    Ok(result) => result,
+                    Err(err) => {
+                        eprintln!("\n{}", err);
+                        continue;
+                    }
+                };
+
+                let normalized_right = match normalized_entry(&field_lang_right, true) {
+                    Ok(result) => result,
+                    Err(err) => {
+                        eprintln!("\n{}", err);
+                        continue;
+                    }
+                };
+
+                if let Some(normalization_cache) = normalization_cache.as_mut() {
+                    normalization_cache.put(records, &normalized_left, &normalized_right)?;
+                }
+                (normalized_left, normalized_right)
+            }
+        };
+
+        let frequency_left = text_frequency(frequencies, &normalized_left.text);
+        let frequency_right = text_frequency(frequencies, &normalized_right.text);
+
+        let collapsed = if db_schema.index_collapsed {
+            Some((
+                collapsed_text(&normalized_left.text),
+                collapsed_text(&normalized_right.text),
+            ))
+        } else {
+            None
+        };
+
+        let cased = if db_schema.index_cased {
+            Some((normalized_left.text.clone(), normalized_right.text.clone()))
+        } else {
+            None
+        };
+
+        let ngrams = if db_schema.index_ngram {
+            Some((normalized_left.text.clone(), normalized_right.text.clone()))
+        } else {
+            None
+        };
+
+        let mut document = doc!(
+            db_schema.key_lang_left => normalized_left.text,
+            db_schema.key_lang_right => normalized_right.text,
+            db_schema.lang_left => field_lang_left,
+            db_schema.lang_right => field_lang_right,
+            db_schema.word_classes => field_word_classes,
+            db_schema.subject_labels => field_subject_labels,
+            db_schema.frequency_lang_left => frequency_left,
+            db_schema.frequency_lang_right => frequency_right,
+            db_schema.source_file => source_file.as_str(),
+        );
+        if let Some((cased_left, cased_right)) = cased {
+            document.add_text(db_schema.cased_lang_left, cased_left);
+            document.add_text(db_schema.cased_lang_right, cased_right);
+        }
+        if let Some((collapsed_left, collapsed_right)) = collapsed {
+            document.add_text(db_schema.collapsed_lang_left, collapsed_left);
+            document.add_text(db_schema.collapsed_lang_right, collapsed_right);
+        }
+        if let Some((ngram_left, ngram_right)) = ngrams {
+            document.add_text(db_schema.ngram_lang_left, ngram_left);
+            document.add_text(db_schema.ngram_lang_right, ngram_right);
+        }
+        if db_schema.index_extra {
+            for annotation in normalized_left.annotations {
+                document.add_text(db_schema.exact_extra_lang_left, annotation);
+            }
+            for annotation in normalized_right.annotations {
+                document.add_text(db_schema.exact_extra_lang_right, annotation);
+            }
+            document.add_text(db_schema.extra_lang_left, normalized_left.extra);
+            document.add_text(db_schema.extra_lang_right, normalized_right.extra);
+        } else {
+            skipped_extra_bytes += normalized_left.extra.len() + normalized_right.extra.len();
+        }
+        index_writer.add_document(document)?;
+    }
+
+    writeln!(progress).unwrap();
+
+    if let Some(normalization_cache) = normalization_cache {
+        normalization_cache.finish()?;
+    }
+
+    if interrupted.load(Ordering::Relaxed) {
+        return Err(DictCliError::ImportInterrupted);
+    }
+
+    // We need to call .commit() explicitly to force the
+    // index_writer to finish processing the documents in the queue,
+    // flush the current index to the disk, and advertise
+    // the existence of new documents.
+    index_writer.commit()?;
+
+    Ok(ImportSummary {
+        records,
+        too_short_records,
+        too_long_records,
+        duplicate_records,
+        skipped_extra_bytes,
+        word_classes,
+        subject_labels,
+    })
+}
+
+/// Whether the error was caused by a full disk
+fn is_disk_full(err: &DictCliError) -> bool {
+    let mut error: &(dyn std::error::Error + 'static) = err;
+    loop {
+        if let Some(io_error) = error.downcast_ref::<std::io::Error>() {
+            if io_error.raw_os_error() == Some(STORAGE_FULL_OS_ERROR) {
+                return true;
+            }
+            // The source of a custom IO error skips the wrapped error itself
+            if let Some(inner_error) = io_error.get_ref() {
+                error = inner_error;
+                continue;
+            }
+        }
+        match error.source() {
+            Some(source) => error = source,
+            None => break,
+        }
+    }
+
+    // Errors of the indexing threads are only passed on as text
+    let message = std::io::Error::from_raw_os_error(STORAGE_FULL_OS_ERROR).to_string();
+    let message = message.split(" (os error").next().unwrap_or_default();
+    err.to_string().contains(message)
+}
+
+#[cfg(unix)]
+const STORAGE_FULL_OS_ERROR: i32 = 28; // ENOSPC
+#[cfg(windows)]
+const STORAGE_FULL_OS_ERROR: i32 = 112; // ERROR_DISK_FULL
+#[cfg(not(any(unix, windows)))]
+const STORAGE_FULL_OS_ERROR: i32 = -1;
+
+/// Removes the database file by file with progress, as large databases on slow disks take a while
+pub(crate) fn remove_database(lang_pair: &str) -> Result<(), DictCliError> {
+    let db_dir = lang_db_dir(lang_pair)?;
+    let lang_pair = normalized_lang_pair(lang_pair)?;
+    if !db_dir.try_exists()? {
+        return Err(DictCliError::NotImported(lang_pair));
+    }
+
+    // The entries of --normalization-cache are of no use without the database either, also a partially written cache
+    let mut cache_files = Vec::with_capacity(2);
+    for purpose in ["normalized", "normalized-new"] {
+        let path = import_temp_dir(&lang_pair, purpose)?;
+        if path.is_file() {
+            cache_files.push(path);
+        }
+    }
+    let mut bytes = directory_size(&db_dir)?;
+    for path in cache_files.iter() {
+        bytes += std::fs::metadata(path)?.len();
+    }
+
+    let mut stdout_lock = stdout().lock();
+    writeln!(
+        stdout_lock,
+        "Deleting database {} ({})...",
+        lang_pair,
+        crate::format_bytes(bytes)
+    )
+    .unwrap();
+
+    let entries = std::fs::read_dir(&db_dir)?.collect::<Result<Vec<_>, _>>()?;
+    for (index, entry) in entries.iter().enumerate() {
+        write!(stdout_lock, "\r-> Removing {}/{}", index + 1, entries.len()).unwrap();
+        stdout_lock.flush().unwrap();
+        if entry.file_type()?.is_dir() {
+            std::fs::remove_dir_all(entry.path())?;
+        } else {
+            std::fs::remove_file(entry.path())?;
+        }
+    }
+    std::fs::remove_dir(&db_dir)?;
+    for path in cache_files {
+        match std::fs::remove_file(path) {
+            Err(err) if err.kind() != ErrorKind::NotFound => return Err(err.into()),
+            _ => {}
+        }
+    }
+
+    writeln!(stdout_lock).unwrap();
+    writeln!(stdout_lock, "Deleted database, freed {}.", crate::format_bytes(bytes)).unwrap();
+    Ok(())
+}
+
+pub(crate) struct DatabaseInfo {
+    pub(crate) documents: u64,
+    pub(crate) bytes: u64,
+}
+
+pub(crate) fn database_info(lang_pair: &str) -> Result<DatabaseInfo, DictCliError> {
+    let db_dir = lang_db_dir(lang_pair)?;
+    let index = open_read_only(&db_dir)?;
+    let documents = index.reader()?.searcher().num_docs();
+    Ok(DatabaseInfo {
+        documents,
+        bytes: directory_size(&db_dir)?,
+    })
+}
+
+fn directory_size<P: AsRef<Path>>(path: P) -> Result<u64, DictCliError> {
+    let mut size = 0;
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        size += if metadata.is_dir() {
+            directory_size(entry.path())?
+        } else {
+            metadata.len()
+        };
+    }
+    Ok(size)
+}
+
+/// Reads every file of the database, so that the first searches find it in the page cache of the system.
+/// Returns the number of bytes read.
+pub(crate) fn warmup_database(lang_pair: &str) -> Result<u64, DictCliError> {
+    read_directory(lang_db_dir(lang_pair)?)
+}
+
+fn read_directory<P: AsRef<Path>>(path: P) -> Result<u64, DictCliError> {
+    let mut bytes = 0;
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        bytes += if entry.file_type()?.is_dir() {
+            read_directory(entry.path())?
+        } else {
+            std::io::copy(&mut File::open(entry.path())?, &mut std::io::sink())?
+        };
+    }
+    Ok(bytes)
+}
+
+#[derive(Clone, Copy)]
+pub(crate) struct SearchOptions {
+    pub(crate) fuzzy_distance: u8,
+    /// Only collect the best scored documents, instead of all matching documents
+    pub(crate) max_documents: Option<usize>,
+    /// Also search the angle bracket annotations
+    pub(crate) search_extra: bool,
+    /// Also search the target language if the source language has few results
+    pub(crate) include_reverse: bool,
+    /// Match every whitespace separated part exactly, see `search_literal_tokens`
+    pub(crate) literal_tokens: bool,
+    /// Match the case of the words, only the keys are searched then
+    pub(crate) case_sensitive: bool,
+    /// Find the words inside of other words, only the keys are searched then
+    pub(crate) contains: bool,
+    /// Match the expression against whole annotations instead of the keys, see `search_exact_extra`
+    pub(crate) exact_extra: bool,
+    /// Print the queries to stderr before they are run
+    pub(crate) print_query: bool,
+    /// Reduce the fuzzy distance of a word that matches more words of the index, see `DatabaseSearch::fuzzy_query`
+    pub(crate) max_fuzzy_expansions: Option<usize>,
+    /// Replace the words that are not in the index by the nearest word that is, see `DatabaseSearch::correct_word`
+    pub(crate) autocorrect: bool,
+    pub(crate) fuzzy_mode: FuzzyMode,
+}
+
+/// How the fuzzy distance applies to a search of several words
+#[derive(Clone, Copy)]
+pub(crate) enum FuzzyMode {
+    /// Every word may differ by the fuzzy distance
+    PerToken,
+    /// The differences of all words together are at most the fuzzy distance
+    Total,
+}
+
+/// With fewer results the target language is searched too, if enabled
+const SPARSE_RESULT_COUNT: usize = 5;
+
+/// Stored fields that are searched on their own with `--search-field`
+#[derive(Clone, Copy)]
+pub(crate) enum LabelField {
+    WordClasses,
+    SubjectLabels,
+}
+
+#[derive(Default)]
+pub(crate) struct SearchResults {
+    pub(crate) documents: Vec<Document>,
+    /// Documents found by searching the target language
+    pub(crate) reverse_documents: Vec<Document>,
+}
+
+/// How the entries were indexed, stored in the database directory at import,
+/// so that the search treats the input the same way without repeating the import options
+#[derive(Clone, Copy)]
+struct DatabaseSettings {
+    index_extra: bool,
+    index_collapsed: bool,
+    index_cased: bool,
+    index_ngram: bool,
+    html_decode: bool,
+    /// Whether the entries were lowercased with the rules of their language, see `language::lowercase`
+    locale_casing: bool,
+    tokenizer: TokenizerKind,
+}
+
+const SETTINGS_FILE: &str = "settings.json";
+
+impl DatabaseSettings {
+    fn from_import_options(options: &ImportOptions) -> Self {
+        Self {
+            index_extra: !options.no_extra_index,
+            index_collapsed: options.index_collapsed,
+            index_cased: options.index_cased,
+            index_ngram: options.index_ngram,
+            html_decode: options.html_decode,
+            locale_casing: true,
+            tokenizer: options.tokenizer,
+        }
+    }
+
+    /// Databases imported before the settings were stored have none
+    fn read(db_dir: &Path) -> Result<Option<Self>, DictCliError> {
+        let content = match std::fs::read_to_string(db_dir.join(SETTINGS_FILE)) {
+            Ok(content) => content,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        let settings: serde_json::Value = serde_json::from_str(&content)
+            .map_err(|_| DictCliError::NotDatabase(db_dir.join(SETTINGS_FILE).display().to_string()))?;
+        let flag = |name: &str, default: bool| settings[name].as_bool().unwrap_or(default);
+        Ok(Some(Self {
+            index_extra: flag("index_extra", true),
+            index_collapsed: flag("index_collapsed", false),
+            // The versions before the flag always indexed the cased keys
+            index_cased: flag("index_cased", true),
+            index_ngram: flag("index_ngram", false),
+            html_decode: flag("html_decode", true),
+            locale_casing: flag("locale_casing", false),
+            tokenizer: settings["tokenizer"]
+                .as_str()
+                .and_then(TokenizerKind::from_name)
+                .unwrap_or(TokenizerKind::Simple),
+        }))
+    }
+
+    fn write(&self, db_dir: &Path) -> Result<(), DictCliError> {
+        let settings = serde_json::json!({
+            "index_extra": self.index_extra,
+            "index_collapsed": self.index_collapsed,
+            "index_cased": self.index_cased,
+            "index_ngram": self.index_ngram,
+            "html_decode": self.html_decode,
+            "locale_casing": self.locale_casing,
+            "tokenizer": self.tokenizer.name(),
+        });
+        std::fs::write(db_dir.join(SETTINGS_FILE), format!("{:#}\n", settings))?;
+        Ok(())
+    }
+}
+
+pub(crate) struct DatabaseSearch {
+    pub(crate) schema: DatabaseSchema,
+    reader: IndexReader,
+    pub(crate) lang_left: String,
+    pub(crate) lang_right: String,
+    /// Whether HTML entities were decoded at import, otherwise they are encoded in the search as well
+    html_decode: bool,
+    /// Whether the keys are indexed without lowercasing, not for databases imported before
+    index_cased: bool,
+    /// Databases imported before the annotations were indexed as a whole can not be searched with `--exact-extra`
+    index_exact_extra: bool,
+}
+
+impl DatabaseSearch {
+    pub(crate) fn new(lang_pair: &str) -> Result<Self, DictCliError> {
+        let db_dir = lang_db_dir(lang_pair)?;
+        let db_lang_pair = normalized_lang_pair(lang_pair)?;
+        if !db_dir.join("meta.json").is_file() {
+            return Err(DictCliError::NotImported(db_lang_pair));
+        }
+        let index = open_read_only(&db_dir)?;
+        // The languages are in the order of the header of the imported file, e.g. en before de for `# EN-DE`
+        let (lang_left, lang_right) =
+            index_languages(&index.schema()).ok_or_else(|| DictCliError::CorruptDatabase(db_lang_pair.clone()))?;
+        if normalized_lang_pair(&format!("{}-{}", lang_left, lang_right))? != db_lang_pair {
+            return Err(DictCliError::SchemaMismatch(db_lang_pair, format!("key_{}", lang_left)));
+        }
+        Self::from_index(index, &lang_left, &lang_right, DatabaseSettings::read(&db_dir)?)
+    }
+
+    /// Loads the segments of the database again, in case the automatic reload missed an import
+    pub(crate) fn reload(&self) -> Result<(), DictCliError> {
+        self.reader.reload()?;
+        Ok(())
+    }
+
+    /// Changes whenever the segments of the database are loaded again, e.g. after an import or `:reload`
+    pub(crate) fn generation(&self) -> u64 {
+        self.reader.searcher().generation().generation_id()
+    }
+
+    /// Opens a database directory, which does not need to be in the data directory
+    pub(crate) fn open_dir(db_dir: &Path) -> Result<Self, DictCliError> {
+        if !db_dir.join("meta.json").is_file() {
+            return Err(DictCliError::NotDatabase(db_dir.display().to_string()));
+        }
+        let index = open_read_only(db_dir)?;
+        let (lang_left, lang_right) =
+            index_languages(&index.schema()).ok_or_else(|| DictCliError::NotDatabase(db_dir.display().to_string()))?;
+        Self::from_index(index, &lang_left, &lang_right, DatabaseSettings::read(db_dir)?)
+    }
+
+    /// Imports the records of a dict.cc file into an index in memory, for tests without files
+    #[cfg(test)]
+    pub(crate) fn in_memory(lang_pair: &str, records: &[u8], options: &ImportOptions) -> Result<Self, DictCliError> {
+        let (lang_left, lang_right) = languages(lang_pair)?;
+        let settings = DatabaseSettings::from_import_options(options);
+        let db_schema = DatabaseSchema::new(lang_left, lang_right, &settings);
+        let index = Index::create_in_ram(db_schema.schema.clone());
+        let format = options.format.unwrap_or(ImportFormat::Dictcc);
+        let input = ImportInput {
+            reader: csv_reader(records, options.delimiter, format),
+            source_file: String::new(),
+            records_count: None,
+            format,
+            normalization_cache: None,
+            interrupted: Arc::new(AtomicBool::new(false)),
+        };
+        write_database(&index, db_schema, input, &HashMap::new(), options, &mut std::io::sink())?;
+        Self::from_index(index, lang_left, lang_right, Some(settings))
+    }
+
+    fn from_index(
+        index: Index,
+        lang_left: &str,
+        lang_right: &str,
+        settings: Option<DatabaseSettings>,
+    ) -> Result<Self, DictCliError> {
+        // An import replaces the database directory, the reader watches meta.json and picks up the new segments
+        let reader = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::OnCommit)
+            .try_into()?;
+        let index_schema = index.schema();
+        let is_indexed = |field_name: &str| {
+            index_schema
+                .get_field(field_name)
+                .map_or(false, |field| index_schema.get_field_entry(field).is_indexed())
+        };
+        let settings = settings.unwrap_or_else(|| {
+            // Older databases tell by their schema, e.g. with --no-extra-index the annotation fields are not indexed
+            DatabaseSettings {
+                index_extra: is_indexed(&extra_field_name(lang_left)),
+                index_collapsed: is_indexed(&collapsed_field_name(lang_left)),
+                index_cased: is_indexed(&cased_field_name(lang_left)),
+                index_ngram: false,
+                html_decode: true,
+                locale_casing: false,
+                tokenizer: TokenizerKind::Simple,
+            }
+        });
+        let schema = DatabaseSchema::new(lang_left, lang_right, &settings);
+        if let Some(field_name) = schema.mismatched_field(&index_schema) {
+            return Err(DictCliError::SchemaMismatch(
+                format!("{}-{}", lang_left, lang_right),
+                field_name,
+            ));
+        }
+        Ok(Self {
+            schema,
+            reader,
+            lang_left: lang_left.to_owned(),
+            lang_right: lang_right.to_owned(),
+            html_decode: settings.html_decode,
+            index_cased: is_indexed(&cased_field_name(lang_left)),
+            index_exact_extra: is_indexed(&exact_extra_field_name(lang_left)),
+        })
+    }
+
+    /// The normalized language pair, as named in the data directory
+    pub(crate) fn language_pair(&self) -> String {
+        format!("{}-{}", self.lang_left, self.lang_right)
+    }
+
+    pub(crate) fn is_reverse_langs(&self, language_from: &str) -> Result<bool, DictCliError> {
+        let language_from = language::resolve(language_from);
+        if language_from == self.lang_left {
+            Ok(false)
+        } else if language_from == self.lang_right {
+            Ok(true)
+        } else {
+            Err(DictCliError::SearchLanguageNotAvailable(
+                language_from.to_owned(),
+                vec![self.lang_left.clone(), self.lang_right.clone()],
+            ))
+        }
+    }
+
+    pub(crate) fn target_language(&self, language_from: &str) -> Result<&str, DictCliError> {
+        let language_from = language::resolve(language_from);
+        if language_from == self.lang_left {
+            Ok(&self.lang_right)
+        } else if language_from == self.lang_right {
+            Ok(&self.lang_left)
+        } else {
+            Err(DictCliError::SearchLanguageNotAvailable(
+                language_from.to_owned(),
+                vec![self.lang_left.clone(), self.lang_right.clone()],
+            ))
+        }
+    }
+
+    /// Reads every document and checks the checksums of the database files
+    pub(crate) fn verify(&self) -> Result<VerifyReport, DictCliError> {
+        let searcher = self.reader.searcher();
+        let corrupted_files = searcher.index().validate_checksum()?.into_iter().sorted().collect();
+        let required_fields = [
+            self.schema.key_lang_left,
+            self.schema.key_lang_right,
+            self.schema.lang_left,
+            self.schema.lang_right,
+        ];
+
+        let mut report = VerifyReport {
+            documents: 0,
+            unreadable_documents: 0,
+            incomplete_documents: 0,
+            corrupted_files,
+        };
+        for doc_address in alive_doc_addresses(&searcher) {
+            report.documents += 1;
+            match searcher.doc(doc_address) {
+                Ok(document) => {
+                    if required_fields.iter().any(|field| document.get_first(*field).is_none()) {
+                        report.incomplete_documents += 1;
+                    }
+                }
+                Err(_) => report.unreadable_documents += 1,
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Every document in stored order
+    pub(crate) fn all_documents(&self) -> Result<Vec<Document>, DictCliError> {
+        let searcher = self.reader.searcher();
+        let documents = alive_doc_addresses(&searcher)
+            .into_iter()
+            .map(|doc_address| searcher.doc(doc_address))
+            .collect::<Result<Vec<Document>, _>>()?;
+        Ok(documents)
+    }
+
+    pub(crate) fn random_documents(&self, count: usize) -> Result<Vec<Document>, DictCliError> {
+        let searcher = self.reader.searcher();
+        let doc_addresses = alive_doc_addresses(&searcher);
+
+        let documents = doc_addresses
+            .choose_multiple(&mut rand::thread_rng(), count)
+            .map(|doc_address| searcher.doc(*doc_address))
+            .collect::<Result<Vec<Document>, _>>()?;
+        Ok(documents)
+    }
+
+    /// Matches the regex against the complete stored entry of the source language.
+    /// The index only knows single tokens, so every stored document is loaded and checked.
+    pub(crate) fn search_regex_full(
+        &self,
+        reverse_langs: bool,
+        regex: &Regex,
+        max_documents: Option<usize>,
+    ) -> Result<Vec<Document>, DictCliError> {
+        let field = if !reverse_langs {
+            self.schema.lang_left
+        } else {
+            self.schema.lang_right
+        };
+
+        let searcher = self.reader.searcher();
+        let mut documents = Vec::new();
+
+        for doc_address in alive_doc_addresses(&searcher) {
+            if max_documents.map_or(false, |max_documents| documents.len() >= max_documents) {
+                break;
+            }
+
+            let document = searcher.doc(doc_address)?;
+            let matches = document
+                .get_first(field)
+                .and_then(|value| value.as_text())
+                .map_or(false, |text| regex.is_match(text));
+            if matches {
+                documents.push(document);
+            }
+        }
+
+        Ok(documents)
+    }
+
+    /// The byte ranges of the words of the entry that match a word of the search
+    pub(crate) fn matched_spans(
+        &self,
+        reverse_langs: bool,
+        entry: &str,
+        expression: &str,
+        fuzzy_distance: u8,
+    ) -> Vec<(Range<usize>, WordMatch)> {
+        let search_words = self.tokenize_search_expression(reverse_langs, &SearchExpression::parse(expression).text());
+        let tokenizer = if !reverse_langs {
+            &self.schema.lowercase_tokenizer_left
+        } else {
+            &self.schema.lowercase_tokenizer_right
+        };
+        let mut token_stream = tokenizer.token_stream(entry);
+        let mut matched_spans = Vec::new();
+
+        while token_stream.advance() {
+            let token = token_stream.token();
+            let word_match = if search_words.contains(&token.text) {
+                WordMatch::Exact
+            } else if fuzzy_distance > 0
+                && search_words
+                    .iter()
+                    // Like the fuzzy search, a transposition counts as one edit
+                    .any(|word| strsim::osa_distance(word, &token.text) <= fuzzy_distance as usize)
+            {
+                WordMatch::Fuzzy
+            } else {
+                continue;
+            };
+            matched_spans.push((token.offset_from..token.offset_to, word_match));
+        }
+
+        matched_spans
+    }
+
+    /// Whether the entry without annotations has every word of the search, without fuzzy distance
+    pub(crate) fn matches_exactly(&self, reverse_langs: bool, entry: &str, expression: &str) -> bool {
+        let search_words = self.tokenize_search_expression(reverse_langs, &SearchExpression::parse(expression).text());
+        let text = normalized_entry(entry, false).map_or_else(|_| entry.to_owned(), |normalized| normalized.text);
+        let entry_words = self.tokenize_search_expression(reverse_langs, &text);
+        !search_words.is_empty() && search_words.iter().all(|word| entry_words.contains(word))
+    }
+
+    /// Tokenizes like the entries of the source language were indexed
+    fn tokenize_search_expression(&self, reverse_langs: bool, expression: &str) -> Vec<String> {
+        let tokenizer = if !reverse_langs {
+            &self.schema.lowercase_tokenizer_left
+        } else {
+            &self.schema.lowercase_tokenizer_right
+        };
+        tokenize(tokenizer, expression)
+    }
+
+    pub(crate) fn search_database(
+        &self,
+        reverse_langs: bool,
+        expression: &str,
+        options: &SearchOptions,
+    ) -> Result<SearchResults, DictCliError> {
+        if expression.trim().is_empty() {
+            return Ok(SearchResults::default());
+        }
+
+        let searcher = self.reader.searcher();
+        // The results are compared with the search as it was given, like the stored entries
+        let search_text = SearchExpression::parse(expression).text();
+        let expression: String = if self.html_decode {
+            expression.nfc().collect()
+        } else {
+            html_escape::encode_text(expression).nfc().collect()
+        };
+        let scorer = self.similarity_scorer(reverse_langs, &search_text);
+        let doc_addresses = self.search_fields(&searcher, reverse_langs, &expression, &scorer, options)?;
+
+        let reverse_doc_addresses = if options.include_reverse && doc_addresses.len() < SPARSE_RESULT_COUNT {
+            let scorer = self.similarity_scorer(!reverse_langs, &search_text);
+            self.search_fields(&searcher, !reverse_langs, &expression, &scorer, options)?
+                .into_iter()
+                .filter(|doc_address| !doc_addresses.contains(doc_address))
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        Ok(SearchResults {
+            documents: load_documents(&searcher, doc_addresses),
+            reverse_documents: load_documents(&searcher, reverse_doc_addresses),
+        })
+    }
+
+    /// Orders the documents by the similarity of their entry of the source language to the search
+    fn similarity_scorer(&self, reverse_langs: bool, search_text: &str) -> SimilarityScorer {
+        let field = if !reverse_langs {
+            self.schema.lang_left
+        } else {
+            self.schema.lang_right
+        };
+        let language = self.schema.field_name(field).to_owned();
+        SimilarityScorer {
+            field,
+            input: similarity_input(&language, search_text),
+            language,
+        }
+    }
+
+    /// Searches the key and extra field of the source language
+    fn search_fields(
+        &self,
+        searcher: &Searcher,
+        reverse_langs: bool,
+        expression: &str,
+        scorer: &SimilarityScorer,
+        options: &SearchOptions,
+    ) -> Result<Vec<DocAddress>, DictCliError> {
+        let (key_field, extra_field, collapsed_field) = if !reverse_langs {
+            (
+                self.schema.key_lang_left,
+                self.schema.extra_lang_left,
+                self.schema.collapsed_lang_left,
+            )
+        } else {
+            (
+                self.schema.key_lang_right,
+                self.schema.extra_lang_right,
+                self.schema.collapsed_lang_right,
+            )
+        };
+
+        if options.literal_tokens {
+            return self.search_literal_tokens(searcher, reverse_langs, key_field, expression, scorer, options);
+        }
+
+        // Every n-gram is within a distance of two of most others, e.g. `ha` of `ba`, so nearly every entry would match
+        if options.fuzzy_distance > 0 && !options.contains && self.schema.tokenizer == TokenizerKind::Ngram {
+            return Err(DictCliError::FuzzyNgramSearch(self.language_pair()));
+        }
+
+        if options.case_sensitive {
+            if !self.index_cased {
+                return Err(DictCliError::MissingIndex(self.language_pair(), "--case-sensitive"));
+            }
+            let cased_field = if !reverse_langs {
+                self.schema.cased_lang_left
+            } else {
+                self.schema.cased_lang_right
+            };
+            return self.search_cased(searcher, cased_field, expression, scorer, options);
+        }
+
+        if options.contains {
+            return self.search_contains(searcher, reverse_langs, key_field, expression, scorer, options);
+        }
+
+        if options.exact_extra {
+            if !self.index_exact_extra {
+                return Err(DictCliError::MissingIndex(self.language_pair(), "--exact-extra"));
+            }
+            let exact_extra_field = if !reverse_langs {
+                self.schema.exact_extra_lang_left
+            } else {
+                self.schema.exact_extra_lang_right
+            };
+            return self.search_exact_extra(searcher, exact_extra_field, expression, scorer, options);
+        }
+
+        let expression = SearchExpression::parse(expression);
+        let words = self.tokenize_search_expression(reverse_langs, &expression.words.join(" "));
+        let phrases: Vec<Vec<String>> = expression
+            .phrases
+            .iter()
+            .map(|phrase| self.tokenize_search_expression(reverse_langs, phrase))
+            .filter(|phrase| !phrase.is_empty())
+            .collect();
+
+        // Nothing to search for, e.g. if every word is excluded
+        if words.is_empty() && phrases.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let excluded_words = self.tokenize_search_expression(reverse_langs, &expression.excluded.join(" "));
+        // With --no-extra-index the annotations cannot be searched, so only the keys exclude
+        let mut excluded_fields = vec![key_field];
+        if self.schema.index_extra {
+            excluded_fields.push(extra_field);
+        }
+        let exclusions = || -> Vec<(Occur, Box<dyn Query>)> {
+            let mut exclusions: Vec<(Occur, Box<dyn Query>)> = Vec::with_capacity(excluded_words.len() * 2);
+            for word in excluded_words.iter() {
+                for field in excluded_fields.iter().copied() {
+                    let query = TermQuery::new(Term::from_field_text(field, word), IndexRecordOption::Basic);
+                    exclusions.push((Occur::MustNot, Box::new(query)));
+                }
+            }
+            exclusions
+        };
+
+        let mut fuzzy_queries: Vec<(Occur, Box<dyn Query>)> = Vec::with_capacity(32);
+        let mut corrected_words = Vec::with_capacity(words.len());
+        for word in words {
+            let word = if options.autocorrect {
+                match self.correct_word(searcher, key_field, extra_field, &word)? {
+                    Some(correction) => {
+                        eprintln!("Searching for {} instead of {}.", correction, word);
+                        correction
+                    }
+                    None => word,
+                }
+            } else {
+                word
+            };
+            corrected_words.push(word);
+        }
+        match options.fuzzy_mode {
+            FuzzyMode::Total if corrected_words.len() > 1 && options.fuzzy_distance > 0 => {
+                let query = self.total_fuzzy_query(searcher, key_field, &corrected_words, options)?;
+                fuzzy_queries.push((Occur::Must, Box::new(query)));
+            }
+            _ => {
+                for word in corrected_words {
+                    let term = Term::from_field_text(key_field, &word);
+                    let query = self.fuzzy_query(searcher, term, options.fuzzy_distance, options)?;
+                    fuzzy_queries.push((Occur::Must, Box::new(query)));
+                }
+            }
+        }
+        for phrase in phrases {
+            fuzzy_queries.push((Occur::Must, self.schema.exact_query(key_field, &phrase)));
+        }
+        fuzzy_queries.extend(exclusions());
+        let boolean_query = BooleanQuery::new(fuzzy_queries);
+        let extra_words = self.tokenize_search_expression(reverse_langs, &expression.text());
+        // Queries whose results are added to the results of the key field
+        let mut alternative_queries: Vec<BooleanQuery> = Vec::with_capacity(2);
+        if options.search_extra && self.schema.index_extra {
+            let mut extra_queries = vec![(Occur::Must, self.schema.exact_query(extra_field, &extra_words))];
+            extra_queries.extend(exclusions());
+            alternative_queries.push(BooleanQuery::new(extra_queries));
+        }
+        if self.schema.index_collapsed {
+            // Without separators the tokenizer only lowercases the whole text
+            if let Some(collapsed_word) = self
+                .tokenize_search_expression(reverse_langs, &collapsed_text(&expression.text()))
+                .pop()
+            {
+                let term = Term::from_field_text(collapsed_field, &collapsed_word);
+                let mut collapsed_queries: Vec<(Occur, Box<dyn Query>)> = vec![(
+                    Occur::Must,
+                    Box::new(self.fuzzy_query(searcher, term, options.fuzzy_distance, options)?),
+                )];
+                collapsed_queries.extend(exclusions());
+                alternative_queries.push(BooleanQuery::new(collapsed_queries));
+            }
+        }
+
+        let mut queries: Vec<&dyn Query> = vec![&boolean_query];
+        queries.extend(alternative_queries.iter().map(|query| query as &dyn Query));
+        self.collect_doc_addresses(searcher, &queries, scorer, options)
+    }
+
+    /// Searches the keys with the case of the expression, without the annotations and collapsed entries
+    fn search_cased(
+        &self,
+        searcher: &Searcher,
+        cased_field: Field,
+        expression: &str,
+        scorer: &SimilarityScorer,
+        options: &SearchOptions,
+    ) -> Result<Vec<DocAddress>, DictCliError> {
+        let tokenizer = &self.schema.cased_tokenizer;
+        let expression = SearchExpression::parse(expression);
+        let mut queries: Vec<(Occur, Box<dyn Query>)> = Vec::with_capacity(32);
+        for word in tokenize(tokenizer, &expression.words.join(" ")) {
+            let term = Term::from_field_text(cased_field, &word);
+            queries.push((
+                Occur::Must,
+                Box::new(self.fuzzy_query(searcher, term, options.fuzzy_distance, options)?),
+            ));
+        }
+        for phrase in expression.phrases.iter() {
+            let phrase = tokenize(tokenizer, phrase);
+            if !phrase.is_empty() {
+                queries.push((Occur::Must, self.schema.exact_query(cased_field, &phrase)));
+            }
+        }
+        if queries.is_empty() {
+            return Ok(Vec::new());
+        }
+        for word in tokenize(tokenizer, &expression.excluded.join(" ")) {
+            let query = TermQuery::new(Term::from_field_text(cased_field, &word), IndexRecordOption::Basic);
+            queries.push((Occur::MustNot, Box::new(query)));
+        }
+
+        self.collect_doc_addresses(searcher, &[&BooleanQuery::new(queries)], scorer, options)
+    }
+
+    /// Searches the words inside of the keys, by their n-grams if indexed with `--ngram`.
+    /// Other databases are searched with a regex on each word of the keys, which scans all words.
+    fn search_contains(
+        &self,
+        searcher: &Searcher,
+        reverse_langs: bool,
+        key_field: Field,
+        expression: &str,
+        scorer: &SimilarityScorer,
+        options: &SearchOptions,
+    ) -> Result<Vec<DocAddress>, DictCliError> {
+        let (ngram_field, ngram_tokenizer) = if !reverse_langs {
+            (self.schema.ngram_lang_left, &self.schema.ngram_tokenizer_left)
+        } else {
+            (self.schema.ngram_lang_right, &self.schema.ngram_tokenizer_right)
+        };
+
+        let mut queries: Vec<(Occur, Box<dyn Query>)> = Vec::with_capacity(32);
+        for word in expression.split_whitespace() {
+            // Databases imported with --tokenizer ngram have the n-grams in the keys
+            let (field, ngrams) = if self.schema.tokenizer == TokenizerKind::Ngram {
+                (key_field, self.tokenize_search_expression(reverse_langs, word))
+            } else if self.schema.index_ngram {
+                (ngram_field, tokenize(ngram_tokenizer, word))
+            } else {
+                (key_field, Vec::new())
+            };
+
+            if !ngrams.is_empty() {
+                let terms = ngrams.iter().map(|ngram| Term::from_field_text(field, ngram)).collect();
+                queries.push((Occur::Must, all_terms_query(terms)));
+            } else {
+                // Also for words shorter than an n-gram
+                for token in self.tokenize_search_expression(reverse_langs, word) {
+                    let pattern = format!(".*{}.*", regex::escape(&token));
+                    let description = format!("{}:/{}/", self.schema.field_name(key_field), pattern);
+                    let query = DescribedQuery {
+                        query: Box::new(RegexQuery::from_pattern(&pattern, key_field)?),
+                        description,
+                    };
+                    queries.push((Occur::Must, Box::new(query)));
+                }
+            }
+        }
+
+        if queries.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        self.collect_doc_addresses(searcher, &[&BooleanQuery::new(queries)], scorer, options)
+    }
+
+    /// The most common word of the keys that is nearest to the word, if neither the keys nor the annotations have
+    /// the word itself. Words within a distance of two are considered.
+    fn correct_word(
+        &self,
+        searcher: &Searcher,
+        key_field: Field,
+        extra_field: Field,
+        word: &str,
+    ) -> Result<Option<String>, DictCliError> {
+        let mut fields = vec![key_field];
+        if self.schema.index_extra {
+            fields.push(extra_field);
+        }
+        for field in fields {
+            if searcher.doc_freq(&Term::from_field_text(field, word))? > 0 {
+                return Ok(None);
+            }
+        }
+        nearest_word(searcher, key_field, word)
+    }
+
+    /// The words of the search that are in no entry, each with the nearest word that is, to suggest them if nothing
+    /// was found
+    pub(crate) fn corrections(
+        &self,
+        reverse_langs: bool,
+        expression: &str,
+    ) -> Result<Vec<(String, String)>, DictCliError> {
+        let searcher = self.reader.searcher();
+        let (key_field, extra_field) = if !reverse_langs {
+            (self.schema.key_lang_left, self.schema.extra_lang_left)
+        } else {
+            (self.schema.key_lang_right, self.schema.extra_lang_right)
+        };
+        let expression = SearchExpression::parse(expression);
+        let mut corrections = Vec::new();
+        for word in self.tokenize_search_expression(reverse_langs, &expression.words.join(" ")) {
+            if let Some(correction) = self.correct_word(&searcher, key_field, extra_field, &word)? {
+                corrections.push((word, correction));
+            }
         }
-        let boolean_query = BooleanQuery::new(fuzzy_queries);
+        Ok(corrections)
+    }
 
-        let fuzzy_results = searcher.search(&boolean_query, &DocSetCollector)?;
-        let extra_results = if extra_terms.len() == 1 {
-            searcher.search(
-                &TermQuery::new(extra_terms.pop().unwrap(), IndexRecordOption::Basic),
-                &DocSetCollector,
-            )
-        } else {
-            searcher.search(&PhraseQuery::new(extra_terms), &DocSetCollector)
-        }?;
+    /// Matches every word within the fuzzy distance of the search in total. Each way to split the distance between
+    /// the words is a conjunction of fuzzy queries, the words of an entry need to match one of them.
+    fn total_fuzzy_query(
+        &self,
+        searcher: &Searcher,
+        key_field: Field,
+        words: &[String],
+        options: &SearchOptions,
+    ) -> Result<BooleanQuery, DictCliError> {
+        let mut word_queries: Vec<Vec<DescribedQuery>> = Vec::with_capacity(words.len());
+        for word in words {
+            let mut queries = Vec::with_capacity(options.fuzzy_distance as usize + 1);
+            for fuzzy_distance in 0..=options.fuzzy_distance {
+                let term = Term::from_field_text(key_field, word);
+                queries.push(self.fuzzy_query(searcher, term, fuzzy_distance, options)?);
+            }
+            word_queries.push(queries);
+        }
 
-        let results: Vec<Document> = (&fuzzy_results | &extra_results)
+        let alternatives = distance_splits(words.len(), options.fuzzy_distance)
             .into_iter()
-            .filter_map(|doc_address| {
-                if let Ok(doc) = searcher.doc(doc_address) {
-                    Some(doc)
-                } else {
-                    eprintln!("Failed to retrieve document.");
-                    None
+            .map(|distances| {
+                let queries: Vec<(Occur, Box<dyn Query>)> = distances
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, fuzzy_distance)| {
+                        let query: Box<dyn Query> = Box::new(word_queries[index][fuzzy_distance as usize].clone());
+                        (Occur::Must, query)
+                    })
+                    .collect();
+                let query: Box<dyn Query> = Box::new(BooleanQuery::new(queries));
+                (Occur::Should, query)
+            })
+            .collect();
+        Ok(BooleanQuery::new(alternatives))
+    }
+
+    /// Matches the term within the fuzzy distance. With `--max-fuzzy-expansions` the distance is reduced
+    /// while the term matches more words of the index, as each matched word is searched.
+    fn fuzzy_query(
+        &self,
+        searcher: &Searcher,
+        term: Term,
+        max_fuzzy_distance: u8,
+        options: &SearchOptions,
+    ) -> Result<DescribedQuery, DictCliError> {
+        let mut fuzzy_distance = max_fuzzy_distance;
+        if let Some(max_fuzzy_expansions) = options.max_fuzzy_expansions {
+            let word = term.as_str().unwrap_or_default();
+            while fuzzy_distance > 0
+                && count_fuzzy_matches(searcher, term.field(), word, fuzzy_distance, max_fuzzy_expansions)?
+                    > max_fuzzy_expansions
+            {
+                fuzzy_distance -= 1;
+            }
+            if fuzzy_distance < max_fuzzy_distance {
+                eprintln!(
+                    "Reduced the fuzzy distance of {} to {}, it matched more than {} words.",
+                    word, fuzzy_distance, max_fuzzy_expansions
+                );
+            }
+        }
+        let description = format!(
+            "{}:{}~{}",
+            self.schema.field_name(term.field()),
+            term.as_str().unwrap_or_default(),
+            fuzzy_distance
+        );
+        Ok(DescribedQuery {
+            query: Box::new(FuzzyTermQuery::new(term, fuzzy_distance, true)),
+            description,
+        })
+    }
+
+    /// The documents matching any of the queries in stored order. With `--search-memory-budget` only the most
+    /// similar ones are collected, which are the first results after sorting, see `SimilarityScorer`.
+    fn collect_doc_addresses(
+        &self,
+        searcher: &Searcher,
+        queries: &[&dyn Query],
+        scorer: &SimilarityScorer,
+        options: &SearchOptions,
+    ) -> Result<Vec<DocAddress>, DictCliError> {
+        if options.print_query {
+            for query in queries {
+                eprintln!("{}", self.schema.describe_query(*query));
+            }
+        }
+        Ok(match options.max_documents {
+            Some(max_documents) => {
+                let top_docs = TopDocs::with_limit(max_documents).custom_score(scorer.clone());
+                let mut results = Vec::with_capacity(max_documents * queries.len());
+                for query in queries {
+                    results.extend(searcher.search(*query, &top_docs)?);
                 }
+                // Equally similar documents in stored order, like the stable sort of all results
+                results
+                    .into_iter()
+                    .sorted_by(|(score1, doc_address1), (score2, doc_address2)| {
+                        score2.cmp(score1).then(doc_address1.cmp(doc_address2))
+                    })
+                    .map(|(_, doc_address)| doc_address)
+                    .unique()
+                    .take(max_documents)
+                    .collect()
+            }
+            None => {
+                let mut results = HashSet::new();
+                for query in queries {
+                    results.extend(searcher.search(*query, &DocSetCollector)?);
+                }
+                // In stored order, so that equally scored results keep their order between searches, e.g. for --skip
+                results.into_iter().sorted().collect()
+            }
+        })
+    }
+
+    /// Searches every whitespace separated part of the expression exactly, without fuzzy matching, quotes or
+    /// exclusions. A part with punctuation like `don't` has to match as a whole.
+    fn search_literal_tokens(
+        &self,
+        searcher: &Searcher,
+        reverse_langs: bool,
+        key_field: Field,
+        expression: &str,
+        scorer: &SimilarityScorer,
+        options: &SearchOptions,
+    ) -> Result<Vec<DocAddress>, DictCliError> {
+        let queries: Vec<(Occur, Box<dyn Query>)> = expression
+            .split_whitespace()
+            .map(|part| self.tokenize_search_expression(reverse_langs, part))
+            .filter(|words| !words.is_empty())
+            .map(|words| (Occur::Must, self.schema.exact_query(key_field, &words)))
+            .collect();
+
+        if queries.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        self.collect_doc_addresses(searcher, &[&BooleanQuery::new(queries)], scorer, options)
+    }
+
+    /// Searches the entries with an annotation that is exactly the expression, e.g. `sth.` finds `<sth.>` but
+    /// neither `<Sth.>` nor `<sb./sth.>`. The angle brackets of the expression are optional.
+    fn search_exact_extra(
+        &self,
+        searcher: &Searcher,
+        exact_extra_field: Field,
+        expression: &str,
+        scorer: &SimilarityScorer,
+        options: &SearchOptions,
+    ) -> Result<Vec<DocAddress>, DictCliError> {
+        let expression = expression.trim();
+        let annotation = expression
+            .strip_prefix('<')
+            .and_then(|expression| expression.strip_suffix('>'))
+            .unwrap_or(expression);
+        let annotation = remove_multiple_whitespace(annotation);
+        if annotation.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let query = TermQuery::new(
+            Term::from_field_text(exact_extra_field, &annotation),
+            IndexRecordOption::Basic,
+        );
+        self.collect_doc_addresses(searcher, &[&query], scorer, options)
+    }
+
+    /// All entries with every word of the expression in the word classes or subject labels, e.g. `[med.]`
+    pub(crate) fn search_label_field(
+        &self,
+        label_field: LabelField,
+        expression: &str,
+    ) -> Result<Vec<Document>, DictCliError> {
+        let field = match label_field {
+            LabelField::WordClasses => self.schema.word_classes,
+            LabelField::SubjectLabels => self.schema.subject_labels,
+        };
+
+        let expression: String = expression.nfc().collect();
+        let queries: Vec<(Occur, Box<dyn Query>)> = tokenize(&self.schema.lowercase_tokenizer, &expression)
+            .iter()
+            .map(|word| -> (Occur, Box<dyn Query>) {
+                let term = Term::from_field_text(field, word);
+                (Occur::Must, Box::new(TermQuery::new(term, IndexRecordOption::Basic)))
             })
             .collect();
-        Ok(results)
+
+        if queries.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let searcher = self.reader.searcher();
+        let doc_addresses = searcher.search(&BooleanQuery::new(queries), &DocSetCollector)?;
+        Ok(load_documents(&searcher, doc_addresses.into_iter().sorted().collect()))
+    }
+
+    /// Every unique word of the source language, read from the term dictionary of the index
+    pub(crate) fn words(&self, reverse_langs: bool) -> Result<BTreeSet<String>, DictCliError> {
+        let key_field = if !reverse_langs {
+            self.schema.key_lang_left
+        } else {
+            self.schema.key_lang_right
+        };
+
+        let searcher = self.reader.searcher();
+        let mut words = BTreeSet::new();
+        for segment_reader in searcher.segment_readers() {
+            let inverted_index = segment_reader.inverted_index(key_field)?;
+            let mut terms = inverted_index.terms().stream()?;
+            while terms.advance() {
+                if let Ok(word) = std::str::from_utf8(terms.key()) {
+                    words.insert(word.to_owned());
+                }
+            }
+        }
+        Ok(words)
     }
 
     pub(crate) fn tab_completions(&self, line: &str, reverse_langs: bool) -> Result<HashSet<String>, DictCliError> {
@@ -405,31 +2598,27 @@ impl DatabaseSearch {
             self.schema.key_lang_right
         };
 
-        let mut tokenized_line = self.tokenize_search_expression(&line);
+        let mut tokenized_line = self.tokenize_search_expression(reverse_langs, &line);
         let last_word = match tokenized_line.pop() {
             Some(word) => word,
             None => return Ok(HashSet::new()),
         };
 
-        let mut start_terms: Vec<Term> = Vec::with_capacity(32);
-        for word in tokenized_line {
-            start_terms.push(Term::from_field_text(key_field, &word));
+        // Words with a typo are completed too, e.g. `hpus` to `house`, see `TabCompletion`
+        let mut last_word_queries: Vec<(Occur, Box<dyn Query>)> = vec![(
+            Occur::Should,
+            Box::new(RegexQuery::from_pattern(&format!("{}.+", last_word), key_field)?),
+        )];
+        if last_word.chars().count() >= MIN_FUZZY_COMPLETION_LEN {
+            let term = Term::from_field_text(key_field, &last_word);
+            last_word_queries.push((Occur::Should, Box::new(FuzzyTermQuery::new_prefix(term, 1, true))));
         }
+        let last_word_results = searcher.search(&BooleanQuery::new(last_word_queries), &DocSetCollector)?;
 
-        let last_word_results = searcher.search(
-            &RegexQuery::from_pattern(&format!("{}.+", last_word), key_field)?,
-            &DocSetCollector,
-        )?;
-
-        let start_results = if start_terms.is_empty() {
+        let start_results = if tokenized_line.is_empty() {
             None
-        } else if start_terms.len() == 1 {
-            Some(searcher.search(
-                &TermQuery::new(start_terms.pop().unwrap(), IndexRecordOption::Basic),
-                &DocSetCollector,
-            )?)
         } else {
-            Some(searcher.search(&PhraseQuery::new(start_terms), &DocSetCollector)?)
+            Some(searcher.search(&self.schema.exact_query(key_field, &tokenized_line), &DocSetCollector)?)
         };
 
         let intersected_results = if let Some(start_results) = &start_results {
@@ -445,7 +2634,7 @@ impl DatabaseSearch {
                     doc.field_values().iter().find_map(|field_value| {
                         if field_value.field == key_field {
                             field_value.value.as_text().and_then(|text| {
-                                if text.starts_with(&line) {
+                                if text.starts_with(&line) || is_fuzzy_prefix(&line, text) {
                                     Some(text.to_owned())
                                 } else {
                                     None
@@ -466,13 +2655,54 @@ impl DatabaseSearch {
     }
 }
 
+/// Shorter words are only completed by their exact prefix, as a typo would match too many words
+const MIN_FUZZY_COMPLETION_LEN: usize = 3;
+
+/// Whether the start of the text differs from the line by one edit, e.g. `hpus` for `house`
+fn is_fuzzy_prefix(line: &str, text: &str) -> bool {
+    let prefix: String = text.chars().take(line.chars().count()).collect();
+    line.chars().count() >= MIN_FUZZY_COMPLETION_LEN && strsim::osa_distance(line, &prefix) <= 1
+}
+
+/// How a word of an entry matched the search
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum WordMatch {
+    Exact,
+    /// Within the fuzzy distance
+    Fuzzy,
+}
+
+/// The result of checking every document of a database
+pub(crate) struct VerifyReport {
+    pub(crate) documents: usize,
+    pub(crate) unreadable_documents: usize,
+    /// Documents without a source or target
+    pub(crate) incomplete_documents: usize,
+    /// Files whose checksum does not match their content
+    pub(crate) corrupted_files: Vec<PathBuf>,
+}
+
+impl VerifyReport {
+    pub(crate) fn is_ok(&self) -> bool {
+        self.unreadable_documents == 0 && self.incomplete_documents == 0 && self.corrupted_files.is_empty()
+    }
+}
+
+#[derive(Default)]
 pub(crate) struct NormalizedEntry {
     pub(crate) text: String,
     pub(crate) extra: String,
+    /// The annotations of `extra` one by one
+    pub(crate) annotations: Vec<String>,
+    /// Pronunciations like `/haʊs/` or `[haʊs]` with their delimiters, see `is_phonetic`
+    pub(crate) phonetic: String,
 }
 
 pub(crate) fn normalized_entry(entry: &str, no_angles: bool) -> Result<NormalizedEntry, DictCliError> {
-    let nodes = parser::parse_entry(entry)?.next().unwrap().into_inner();
+    let nodes = match parser::parse_entry(entry)?.next() {
+        Some(expr) => expr.into_inner(),
+        None => return Ok(NormalizedEntry::default()),
+    };
 
     let text = nodes
         .clone()
@@ -482,7 +2712,17 @@ pub(crate) fn normalized_entry(entry: &str, no_angles: bool) -> Result<Normalize
         })
         .join(" ");
 
-    let extra = nodes
+    let phonetic = nodes
+        .clone()
+        .filter_map(|node| match node.as_rule() {
+            // The grammar only parses slashes around phonetic symbols as a pronunciation
+            parser::Rule::slash => Some(node.as_str()),
+            parser::Rule::square if is_phonetic(node.as_str()) => Some(node.as_str()),
+            _ => None,
+        })
+        .join(" ");
+
+    let annotations: Vec<&str> = nodes
         .filter_map(|node| match node.as_rule() {
             parser::Rule::angle => {
                 let text = node.as_str();
@@ -490,14 +2730,28 @@ pub(crate) fn normalized_entry(entry: &str, no_angles: bool) -> Result<Normalize
             }
             _ => None,
         })
-        .join(" ");
+        .collect();
 
     Ok(NormalizedEntry {
         text: remove_multiple_whitespace(&text),
-        extra: remove_multiple_whitespace(extra.trim()),
+        extra: remove_multiple_whitespace(annotations.join(" ").trim()),
+        annotations: annotations
+            .into_iter()
+            .map(remove_multiple_whitespace)
+            .filter(|annotation| !annotation.is_empty())
+            .collect(),
+        phonetic: remove_multiple_whitespace(&phonetic),
     })
 }
 
+/// Whether the text contains characters that only the International Phonetic Alphabet uses, like `ə` or the
+/// stress mark `ˈ`, the same as `phonetic_symbol` of the grammar. dict.cc has no field for pronunciations, so the square brackets of a label like `[coll.]` and
+/// those of a pronunciation like `[ˈhaʊs]` are told apart by their characters.
+fn is_phonetic(text: &str) -> bool {
+    text.chars()
+        .any(|c| matches!(c, '\u{250}'..='\u{2AF}' | 'ˈ' | 'ˌ' | 'ː' | 'ˑ'))
+}
+
 /// https://stackoverflow.com/questions/71864137/whats-the-ideal-way-to-trim-extra-spaces-from-a-string
 fn remove_multiple_whitespace(s: &str) -> String {
     let mut result = String::with_capacity(s.len());
@@ -509,3 +2763,685 @@ fn remove_multiple_whitespace(s: &str) -> String {
     });
     result
 }
+
+/// The languages of a database in the order of its fields, which is the order of the imported file
+fn index_languages(index_schema: &Schema) -> Option<(String, String)> {
+    let mut key_langs = index_schema
+        .fields()
+        .filter_map(|(_, field_entry)| field_entry.name().strip_prefix("key_"));
+    match (key_langs.next(), key_langs.next()) {
+        (Some(lang_left), Some(lang_right)) => Some((lang_left.to_owned(), lang_right.to_owned())),
+        _ => None,
+    }
+}
+
+/// Opens a database for searching, see `ReadOnlyDirectory`.
+/// Any number of processes can search a database at the same time, also during an import of its language pair.
+fn open_read_only(db_dir: &Path) -> Result<Index, DictCliError> {
+    let directory = ReadOnlyDirectory::open(db_dir).map_err(TantivyError::from)?;
+    Ok(Index::open(directory)?)
+}
+
+/// Lowercases the tokens like `language::lowercase`, as the `LowerCaser` of tantivy only knows the default casing
+#[derive(Clone)]
+struct LanguageLowerCaser {
+    language: String,
+}
+
+impl TokenFilter for LanguageLowerCaser {
+    fn transform<'a>(&self, token_stream: BoxTokenStream<'a>) -> BoxTokenStream<'a> {
+        BoxTokenStream::from(LanguageLowerCaserTokenStream {
+            language: self.language.clone(),
+            tail: token_stream,
+        })
+    }
+}
+
+struct LanguageLowerCaserTokenStream<'a> {
+    language: String,
+    tail: BoxTokenStream<'a>,
+}
+
+impl TokenStream for LanguageLowerCaserTokenStream<'_> {
+    fn advance(&mut self) -> bool {
+        if !self.tail.advance() {
+            return false;
+        }
+        let text = &mut self.tail.token_mut().text;
+        *text = language::lowercase(&self.language, text);
+        true
+    }
+
+    fn token(&self) -> &Token {
+        self.tail.token()
+    }
+
+    fn token_mut(&mut self) -> &mut Token {
+        self.tail.token_mut()
+    }
+}
+
+fn tokenize(tokenizer: &TextAnalyzer, text: &str) -> Vec<String> {
+    let mut token_stream = tokenizer.token_stream(text);
+    let mut tokens: Vec<String> = Vec::with_capacity(32);
+    while token_stream.advance() {
+        tokens.push(std::mem::take(&mut token_stream.token_mut().text));
+    }
+    tokens
+}
+
+/// Every way to split the fuzzy distance between the words, e.g. `[2, 0]`, `[1, 1]` and `[0, 2]` for two words and
+/// a distance of two. Fewer differences in total are matched too, as a fuzzy query also matches smaller distances.
+fn distance_splits(words: usize, fuzzy_distance: u8) -> Vec<Vec<u8>> {
+    if words == 1 {
+        return vec![vec![fuzzy_distance]];
+    }
+    (0..=fuzzy_distance)
+        .rev()
+        .flat_map(|first| {
+            distance_splits(words - 1, fuzzy_distance - first)
+                .into_iter()
+                .map(move |mut rest| {
+                    rest.insert(0, first);
+                    rest
+                })
+        })
+        .collect()
+}
+
+/// The largest fuzzy distance, as tantivy only builds Levenshtein automata up to it
+pub(crate) const MAX_FUZZY_DISTANCE: u8 = 2;
+
+thread_local! {
+    /// The automaton builders by distance, as building one takes longer than matching a word with it
+    static AUTOMATON_BUILDERS: RefCell<[Option<LevenshteinAutomatonBuilder>; MAX_FUZZY_DISTANCE as usize + 1]> =
+        RefCell::new(Default::default());
+}
+
+/// The automaton matching the words within the fuzzy distance of the word, which is capped at `MAX_FUZZY_DISTANCE`
+fn fuzzy_dfa(word: &str, fuzzy_distance: u8) -> DFA {
+    let fuzzy_distance = fuzzy_distance.min(MAX_FUZZY_DISTANCE);
+    AUTOMATON_BUILDERS.with(|builders| {
+        builders.borrow_mut()[fuzzy_distance as usize]
+            .get_or_insert_with(|| LevenshteinAutomatonBuilder::new(fuzzy_distance, true))
+            .build_dfa(word)
+    })
+}
+
+/// The words of the field within the fuzzy distance of the word, counted up to just over the limit.
+/// Words in several segments are counted once per segment.
+fn count_fuzzy_matches(
+    searcher: &Searcher,
+    field: Field,
+    word: &str,
+    fuzzy_distance: u8,
+    limit: usize,
+) -> Result<usize, DictCliError> {
+    let dfa = fuzzy_dfa(word, fuzzy_distance);
+    let mut count = 0;
+    for segment_reader in searcher.segment_readers() {
+        let inverted_index = segment_reader.inverted_index(field)?;
+        let mut terms = inverted_index.terms().search(FuzzyAutomaton(&dfa)).into_stream()?;
+        while terms.advance() {
+            count += 1;
+            if count > limit {
+                return Ok(count);
+            }
+        }
+    }
+    Ok(count)
+}
+
+/// The word of the field with the fewest edits to the word, of those the one in the most entries
+fn nearest_word(searcher: &Searcher, field: Field, word: &str) -> Result<Option<String>, DictCliError> {
+    for distance in 1..=MAX_FUZZY_DISTANCE {
+        let dfa = fuzzy_dfa(word, distance);
+        let mut doc_freqs: HashMap<Vec<u8>, u64> = HashMap::new();
+        for segment_reader in searcher.segment_readers() {
+            let inverted_index = segment_reader.inverted_index(field)?;
+            let mut terms = inverted_index.terms().search(FuzzyAutomaton(&dfa)).into_stream()?;
+            while terms.advance() {
+                *doc_freqs.entry(terms.key().to_vec()).or_insert(0) += u64::from(terms.value().doc_freq);
+            }
+        }
+        // Equally common words are ordered alphabetically, so that the same word is suggested every time
+        let nearest = doc_freqs
+            .into_iter()
+            .max_by(|(word1, doc_freq1), (word2, doc_freq2)| doc_freq1.cmp(doc_freq2).then_with(|| word2.cmp(word1)));
+        if let Some((nearest, _)) = nearest {
+            return Ok(String::from_utf8(nearest).ok());
+        }
+    }
+    Ok(None)
+}
+
+/// The search as it is compared with the entries, lowercased with the rules of the language
+pub(crate) fn similarity_input(language: &str, search_text: &str) -> String {
+    language::lowercase(language, search_text).nfc().collect()
+}
+
+/// The similarity of the entry without brackets and of its angle bracket annotations to the search, from 0 to 1.
+/// The search is expected as returned by `similarity_input`.
+pub(crate) fn similarity(language: &str, normalized: &NormalizedEntry, input: &str) -> (f64, f64) {
+    let lowercase = |text: &str| language::lowercase(language, text);
+    (
+        strsim::sorensen_dice(&lowercase(&normalized.text).replace(['(', ')'], ""), input),
+        strsim::sorensen_dice(&lowercase(&normalized.extra), input),
+    )
+}
+
+/// The better of both similarities, scaled to 0 to 1000 like the results are sorted
+pub(crate) fn scaled_similarity((text, extra): (f64, f64)) -> u16 {
+    (text.max(extra) * 1000.0) as u16
+}
+
+/// Scores the documents like the results are sorted, by the similarity of an entry to the search.
+/// The collected best documents are then the same as the first results of a search that collects all documents.
+#[derive(Clone)]
+struct SimilarityScorer {
+    field: Field,
+    language: String,
+    /// The search, see `similarity_input`
+    input: String,
+}
+
+impl CustomScorer<u16> for SimilarityScorer {
+    type Child = SimilaritySegmentScorer;
+
+    fn segment_scorer(&self, segment_reader: &SegmentReader) -> tantivy::Result<SimilaritySegmentScorer> {
+        Ok(SimilaritySegmentScorer {
+            scorer: self.clone(),
+            store_reader: segment_reader.get_store_reader()?,
+        })
+    }
+}
+
+struct SimilaritySegmentScorer {
+    scorer: SimilarityScorer,
+    store_reader: StoreReader,
+}
+
+impl CustomSegmentScorer<u16> for SimilaritySegmentScorer {
+    fn score(&mut self, doc: DocId) -> u16 {
+        let document = match self.store_reader.get(doc) {
+            Ok(document) => document,
+            Err(_) => return 0,
+        };
+        let entry = document
+            .get_first(self.scorer.field)
+            .and_then(|value| value.as_text())
+            .unwrap_or_default();
+        match normalized_entry(entry, false) {
+            Ok(normalized) => scaled_similarity(similarity(&self.scorer.language, &normalized, &self.scorer.input)),
+            Err(_) => 0,
+        }
+    }
+}
+
+/// Walks the term dictionary with a Levenshtein automaton, like the fuzzy queries of tantivy
+struct FuzzyAutomaton<'a>(&'a DFA);
+
+impl Automaton for FuzzyAutomaton<'_> {
+    type State = u32;
+
+    fn start(&self) -> u32 {
+        self.0.initial_state()
+    }
+
+    fn is_match(&self, state: &u32) -> bool {
+        matches!(self.0.distance(*state), Distance::Exact(_))
+    }
+
+    fn can_match(&self, state: &u32) -> bool {
+        *state != levenshtein_automata::SINK_STATE
+    }
+
+    fn accept(&self, state: &u32, byte: u8) -> u32 {
+        self.0.transition(*state, byte)
+    }
+}
+
+/// Matches every term, in any order
+fn all_terms_query(terms: Vec<Term>) -> Box<dyn Query> {
+    Box::new(BooleanQuery::new(
+        terms
+            .into_iter()
+            .map(|term| -> (Occur, Box<dyn Query>) {
+                (Occur::Must, Box::new(TermQuery::new(term, IndexRecordOption::Basic)))
+            })
+            .collect(),
+    ))
+}
+
+fn load_documents(searcher: &Searcher, doc_addresses: Vec<DocAddress>) -> Vec<Document> {
+    doc_addresses
+        .into_iter()
+        .filter_map(|doc_address| {
+            if let Ok(doc) = searcher.doc(doc_address) {
+                Some(doc)
+            } else {
+                eprintln!("Failed to retrieve document.");
+                None
+            }
+        })
+        .collect()
+}
+
+fn alive_doc_addresses(searcher: &Searcher) -> Vec<DocAddress> {
+    searcher
+        .segment_readers()
+        .iter()
+        .enumerate()
+        .flat_map(|(segment_ord, segment_reader)| {
+            segment_reader
+                .doc_ids_alive()
+                .map(move |doc_id| DocAddress::new(segment_ord as u32, doc_id))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search_options(fuzzy_distance: u8) -> SearchOptions {
+        SearchOptions {
+            fuzzy_distance,
+            max_documents: None,
+            search_extra: false,
+            include_reverse: false,
+            literal_tokens: false,
+            case_sensitive: false,
+            contains: false,
+            exact_extra: false,
+            print_query: false,
+            max_fuzzy_expansions: None,
+            autocorrect: false,
+            fuzzy_mode: FuzzyMode::PerToken,
+        }
+    }
+
+    /// The stored entries of the left language of the documents
+    fn left_entries(db_search: &DatabaseSearch, documents: &[Document]) -> Vec<String> {
+        documents
+            .iter()
+            .filter_map(|document| document.get_first(db_search.schema.lang_left)?.as_text())
+            .map(str::to_owned)
+            .collect()
+    }
+
+    /// Imports the records into the database directory, adding to the entries already in it
+    fn write_records(db_dir: &Path, records: &[u8], options: &ImportOptions) -> Result<(), DictCliError> {
+        write_lang_pair_records(db_dir, "de-en", records, options)
+    }
+
+    /// Like an import of a file with the header of the language pair, which is not always in alphabetical order
+    fn write_lang_pair_records(
+        db_dir: &Path,
+        lang_pair: &str,
+        records: &[u8],
+        options: &ImportOptions,
+    ) -> Result<(), DictCliError> {
+        let (lang_left, lang_right) = languages(lang_pair)?;
+        let settings = DatabaseSettings::from_import_options(options);
+        let db_schema = DatabaseSchema::new(lang_left, lang_right, &settings);
+        let index = if db_dir.join("meta.json").is_file() {
+            Index::open_in_dir(db_dir)?
+        } else {
+            Index::create_in_dir(db_dir, db_schema.schema.clone())?
+        };
+        let input = ImportInput {
+            reader: csv_reader(records, options.delimiter, ImportFormat::Dictcc),
+            source_file: String::new(),
+            records_count: None,
+            format: ImportFormat::Dictcc,
+            normalization_cache: None,
+            interrupted: Arc::new(AtomicBool::new(false)),
+        };
+        write_database(&index, db_schema, input, &HashMap::new(), options, &mut std::io::sink())?;
+        settings.write(db_dir)
+    }
+
+    #[test]
+    fn concurrent_searches_during_import() {
+        let db_dir = std::env::temp_dir().join(format!("dictcc-cli-concurrent-{}", std::process::id()));
+        std::fs::create_dir_all(&db_dir).unwrap();
+        let options = ImportOptions::default();
+        write_records(&db_dir, b"Haus\thouse\n", &options).unwrap();
+        let db_search = DatabaseSearch::open_dir(&db_dir).unwrap();
+
+        std::thread::scope(|scope| {
+            // Every thread opens the database on its own like another process, and one is shared
+            let searches: Vec<_> = (0..4)
+                .map(|thread| {
+                    let (db_dir, db_search) = (&db_dir, &db_search);
+                    scope.spawn(move || {
+                        let own_search = DatabaseSearch::open_dir(db_dir).unwrap();
+                        for _ in 0..50 {
+                            let db_search = if thread % 2 == 0 { &own_search } else { db_search };
+                            let results = db_search.search_database(false, "Haus", &search_options(0)).unwrap();
+                            assert_eq!(left_entries(db_search, &results.documents), ["Haus"]);
+                        }
+                    })
+                })
+                .collect();
+            // The dummy locks of the readers must not keep the writer from locking the index
+            let writer = scope.spawn(|| write_records(&db_dir, b"Baum\ttree\n", &options));
+            for search in searches {
+                search.join().unwrap();
+            }
+            writer.join().unwrap().unwrap();
+        });
+
+        db_search.reload().unwrap();
+        let results = db_search.search_database(false, "Baum", &search_options(0)).unwrap();
+        assert_eq!(left_entries(&db_search, &results.documents), ["Baum"]);
+        std::fs::remove_dir_all(&db_dir).unwrap();
+    }
+
+    #[test]
+    fn open_database_of_unsorted_header() {
+        let data_dir = std::env::temp_dir().join(format!("dictcc-cli-data-{}", std::process::id()));
+        std::env::set_var("DICTCC_DATA_DIR", &data_dir);
+        // A file starting with `# EN-DE` is stored as de-en, with the English fields first
+        let db_dir = lang_db_dir("en-de").unwrap();
+        assert!(db_dir.ends_with("de-en"));
+        std::fs::create_dir_all(&db_dir).unwrap();
+        write_lang_pair_records(&db_dir, "en-de", b"house\tHaus {n}\n", &ImportOptions::default()).unwrap();
+
+        for lang_pair in ["de-en", "en-de"] {
+            let db_search = DatabaseSearch::new(lang_pair).unwrap();
+            assert_eq!(db_search.language_pair(), "en-de");
+            assert!(db_search.is_reverse_langs("de").unwrap());
+            let results = db_search.search_database(true, "Haus", &search_options(0)).unwrap();
+            assert_eq!(left_entries(&db_search, &results.documents), ["house"]);
+        }
+        std::fs::remove_dir_all(&data_dir).unwrap();
+    }
+
+    #[test]
+    fn import_vocabulary_list() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/vocab.txt");
+        let (format, lang_pair) = read_header(&path, b'\t').unwrap();
+        assert!(format == ImportFormat::Vocab);
+        assert_eq!(lang_pair, "de-en");
+
+        let options = ImportOptions {
+            format: Some(format),
+            ..ImportOptions::default()
+        };
+        let db_search = DatabaseSearch::in_memory(&lang_pair, &std::fs::read(&path).unwrap(), &options).unwrap();
+        let documents = db_search.all_documents().unwrap();
+        let entries: Vec<(&str, &str, &str)> = documents
+            .iter()
+            .map(|document| {
+                let field = |field| document.get_first(field).and_then(|value| value.as_text()).unwrap();
+                (
+                    field(db_search.schema.lang_left),
+                    field(db_search.schema.lang_right),
+                    field(db_search.schema.word_classes),
+                )
+            })
+            .collect();
+        // The comment column is left out
+        assert_eq!(
+            entries,
+            [("Haus {n}", "house", ""), ("auswendig lernen", "to learn by heart", "")]
+        );
+        let results = db_search.search_database(false, "Haus", &search_options(0)).unwrap();
+        assert_eq!(left_entries(&db_search, &results.documents), ["Haus {n}"]);
+
+        let options = ImportOptions {
+            format: Some(ImportFormat::Dictcc),
+            ..ImportOptions::default()
+        };
+        assert!(matches!(
+            read_import_header(&path, &options),
+            Err(DictCliError::ImportFormatMismatch("dictcc", "vocab"))
+        ));
+    }
+
+    #[test]
+    fn html_entities_without_decoding() {
+        for (html_decode, entry) in [(true, "rock & roll"), (false, "rock &amp; roll")] {
+            let options = ImportOptions {
+                html_decode,
+                ..ImportOptions::default()
+            };
+            let db_search = DatabaseSearch::in_memory("en-de", b"rock &amp; roll\tRock 'n' Roll\n", &options).unwrap();
+            let results = db_search
+                .search_database(false, "rock & roll", &search_options(0))
+                .unwrap();
+            assert_eq!(left_entries(&db_search, &results.documents), [entry]);
+        }
+    }
+
+    #[test]
+    fn case_sensitive_search_needs_cased_index() {
+        let records = b"US\tUSA\nus\tuns\n";
+        let options = SearchOptions {
+            case_sensitive: true,
+            ..search_options(0)
+        };
+        let db_search = DatabaseSearch::in_memory("en-de", records, &ImportOptions::default()).unwrap();
+        assert!(matches!(
+            db_search.search_database(false, "US", &options),
+            Err(DictCliError::MissingIndex(_, "--case-sensitive"))
+        ));
+
+        let import_options = ImportOptions {
+            index_cased: true,
+            ..ImportOptions::default()
+        };
+        let db_search = DatabaseSearch::in_memory("en-de", records, &import_options).unwrap();
+        let results = db_search.search_database(false, "US", &options).unwrap();
+        assert_eq!(left_entries(&db_search, &results.documents), ["US"]);
+    }
+
+    #[test]
+    fn describe_fuzzy_query() {
+        let db_search = DatabaseSearch::in_memory("de-en", b"Haus {n}\thouse\n", &ImportOptions::default()).unwrap();
+        let term = Term::from_field_text(db_search.schema.key_lang_left, "haus");
+        let fuzzy_query = db_search
+            .fuzzy_query(&db_search.reader.searcher(), term, 1, &search_options(1))
+            .unwrap();
+        let excluded = Term::from_field_text(db_search.schema.key_lang_left, "maus");
+        let query = BooleanQuery::new(vec![
+            (Occur::Must, Box::new(fuzzy_query)),
+            (
+                Occur::MustNot,
+                Box::new(TermQuery::new(excluded, IndexRecordOption::Basic)),
+            ),
+        ]);
+        assert_eq!(db_search.schema.describe_query(&query), "(+key_de:haus~1 -key_de:maus)");
+    }
+
+    #[test]
+    fn ngram_tokenizer_without_fuzzy_distance() {
+        let options = ImportOptions {
+            tokenizer: TokenizerKind::Ngram,
+            ..ImportOptions::default()
+        };
+        let db_search = DatabaseSearch::in_memory("de-en", b"Haus {n}\thouse\nBaum {m}\ttree\n", &options).unwrap();
+        let results = db_search.search_database(false, "aus", &search_options(0)).unwrap();
+        assert_eq!(left_entries(&db_search, &results.documents), ["Haus {n}"]);
+        assert!(matches!(
+            db_search.search_database(false, "Haus", &search_options(1)),
+            Err(DictCliError::FuzzyNgramSearch(_))
+        ));
+    }
+
+    #[test]
+    fn lowercase_with_language_rules() {
+        let db_search = DatabaseSearch::in_memory(
+            "de-tr",
+            b"Stra\xc3\x9fe\tcadde\nIstanbul\t\xc4\xb0stanbul\n",
+            &ImportOptions::default(),
+        )
+        .unwrap();
+        for (reverse_langs, expression, entry) in [
+            (false, "STRASSE", "Straße"),
+            (false, "strasse", "Straße"),
+            (true, "istanbul", "Istanbul"),
+            (true, "İSTANBUL", "Istanbul"),
+        ] {
+            let results = db_search
+                .search_database(reverse_langs, expression, &search_options(0))
+                .unwrap();
+            assert_eq!(left_entries(&db_search, &results.documents), [entry], "{}", expression);
+        }
+        // The dotless I of Turkish is another letter
+        let results = db_search.search_database(true, "ISTANBUL", &search_options(0)).unwrap();
+        assert!(results.documents.is_empty());
+    }
+
+    #[test]
+    fn normalization_cache_keeps_separators() {
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("dictcc-cli-normalized-{}", std::process::id()));
+        let new_path = dir.join(format!("dictcc-cli-normalized-new-{}", std::process::id()));
+        let entry = |text: &str, annotations: &[&str]| NormalizedEntry {
+            text: text.to_owned(),
+            extra: "<a\tb>".to_owned(),
+            annotations: annotations.iter().map(|annotation| annotation.to_string()).collect(),
+            phonetic: "\\t\r".to_owned(),
+        };
+
+        let mut cache = NormalizationCache::open_path(path.clone(), new_path.clone(), 1).unwrap();
+        assert!(matches!(cache, NormalizationCache::Write { .. }));
+        cache
+            .put(2, &entry("a\tb", &["a\tb", ""]), &entry("c\nd\\", &[]))
+            .unwrap();
+        cache.finish().unwrap();
+
+        let mut cache = NormalizationCache::open_path(path.clone(), new_path.clone(), 1).unwrap();
+        assert!(cache.get(1).unwrap().is_none());
+        let (left, right) = cache.get(2).unwrap().unwrap();
+        for (cached, text, annotations) in [(left, "a\tb", vec!["a\tb", ""]), (right, "c\nd\\", vec![])] {
+            assert_eq!(cached.text, text);
+            assert_eq!(cached.extra, "<a\tb>");
+            assert_eq!(cached.annotations, annotations);
+            assert_eq!(cached.phonetic, "\\t\r");
+        }
+        // Another file or other options have another key
+        let cache = NormalizationCache::open_path(path.clone(), new_path.clone(), 2).unwrap();
+        assert!(matches!(cache, NormalizationCache::Write { .. }));
+        drop(cache);
+        std::fs::remove_file(path).unwrap();
+        std::fs::remove_file(new_path).unwrap();
+    }
+
+    #[test]
+    fn fnv1a_hash_is_stable() {
+        let mut hasher = Fnv1aHasher::default();
+        assert_eq!(hasher.finish(), 0xcbf2_9ce4_8422_2325);
+        hasher.write(b"a");
+        assert_eq!(hasher.finish(), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn fuzzy_distance_per_token_and_total() {
+        let db_search = DatabaseSearch::in_memory(
+            "en-de",
+            b"river bank\tUfer {n}\nriver band\tFlussband {n}\nbank\tBank {f}\n",
+            &ImportOptions::default(),
+        )
+        .unwrap();
+        let search = |expression: &str, fuzzy_distance: u8, fuzzy_mode: FuzzyMode| {
+            let options = SearchOptions {
+                fuzzy_mode,
+                ..search_options(fuzzy_distance)
+            };
+            let results = db_search.search_database(false, expression, &options).unwrap();
+            left_entries(&db_search, &results.documents)
+                .into_iter()
+                .sorted()
+                .collect::<Vec<_>>()
+        };
+
+        // One typo in each word is within a distance of 1 per word, but not of 1 in total
+        assert_eq!(search("rivr bnk", 1, FuzzyMode::PerToken), ["river bank"]);
+        assert!(search("rivr bnk", 1, FuzzyMode::Total).is_empty());
+        assert_eq!(search("rivr bnk", 2, FuzzyMode::Total), ["river bank"]);
+        // A distance of 1 per word also reaches band, in total it is spent on the typo of river
+        assert_eq!(
+            search("rivr bank", 1, FuzzyMode::PerToken),
+            ["river band", "river bank"]
+        );
+        assert_eq!(search("rivr bank", 1, FuzzyMode::Total), ["river bank"]);
+        // A single word is searched the same way in both modes
+        assert_eq!(
+            search("bnk", 1, FuzzyMode::PerToken),
+            search("bnk", 1, FuzzyMode::Total)
+        );
+    }
+
+    #[test]
+    fn distance_splits_of_words() {
+        assert_eq!(distance_splits(1, 2), [[2]]);
+        assert_eq!(distance_splits(2, 1), [[1, 0], [0, 1]]);
+        assert_eq!(distance_splits(2, 2), [[2, 0], [1, 1], [0, 2]]);
+        assert_eq!(distance_splits(3, 1), [[1, 0, 0], [0, 1, 0], [0, 0, 1]]);
+    }
+
+    #[test]
+    fn excluded_words_without_extra_index() {
+        let records = b"bank <river>\tUfer {n}\nbank\tBank {f}\nriver bank\tFlussufer {n}\n";
+        for (no_extra_index, entries) in [(false, vec!["bank"]), (true, vec!["bank", "bank <river>"])] {
+            let options = ImportOptions {
+                no_extra_index,
+                ..ImportOptions::default()
+            };
+            let db_search = DatabaseSearch::in_memory("en-de", records, &options).unwrap();
+            let results = db_search
+                .search_database(false, "bank -river", &search_options(0))
+                .unwrap();
+            let found = left_entries(&db_search, &results.documents);
+            assert_eq!(found.into_iter().sorted().collect::<Vec<_>>(), entries);
+        }
+    }
+
+    #[test]
+    fn normalized_entry_with_slashes() {
+        let normalized = normalized_entry("house /haʊs/ {n}", false).unwrap();
+        assert_eq!(normalized.text, "house");
+        assert_eq!(normalized.phonetic, "/haʊs/");
+
+        // Slashes between alternatives keep the brackets around them
+        let normalized = normalized_entry("der / die Verwandte {m} {f} / das", false).unwrap();
+        assert_eq!(normalized.text, "der / die Verwandte / das");
+        assert_eq!(normalized.phonetic, "");
+        let normalized = normalized_entry("sich <Dat.> / jdm. etw. <Akk.> / geben", false).unwrap();
+        assert_eq!(normalized.text, "sich / jdm. etw. / geben");
+        assert_eq!(normalized.annotations, ["<Dat.>", "<Akk.>"]);
+
+        let records = "der / die Verwandte {m} {f} / das\trelative\nsich <Dat.> / jdm. etw. <Akk.> / geben\tto give\n";
+        let db_search = DatabaseSearch::in_memory("de-en", records.as_bytes(), &ImportOptions::default()).unwrap();
+        for expression in ["f", "m"] {
+            let results = db_search
+                .search_database(false, expression, &search_options(0))
+                .unwrap();
+            assert!(results.documents.is_empty(), "{}", expression);
+        }
+        let options = SearchOptions {
+            exact_extra: true,
+            ..search_options(0)
+        };
+        let results = db_search.search_database(false, "Akk.", &options).unwrap();
+        assert_eq!(
+            left_entries(&db_search, &results.documents),
+            ["sich <Dat.> / jdm. etw. <Akk.> / geben"]
+        );
+    }
+
+    #[test]
+    fn normalized_entry_of_empty_input() {
+        for entry in ["", "  \t"] {
+            let normalized = normalized_entry(entry, true).unwrap();
+            assert_eq!(normalized.text, "");
+            assert_eq!(normalized.extra, "");
+            assert!(normalized.annotations.is_empty());
+            assert_eq!(normalized.phonetic, "");
+        }
+    }
+}
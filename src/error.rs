@@ -1,6 +1,13 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use itertools::Itertools;
+use regex::Regex;
+use serde_json::json;
 use thiserror::Error;
 
-use crate::parser;
+use crate::{language, parser, style};
+
+static JSON_ERRORS: AtomicBool = AtomicBool::new(false);
 
 #[derive(Error, Debug)]
 pub enum DictCliError {
@@ -10,18 +17,191 @@ pub enum DictCliError {
     NotDirectory(String),
     #[error("The dictionary has already been imported. Use --force to overwrite it.")]
     AlreadyImported,
+    #[error("The disk is full, the partial database {0} has been removed. Free up some space and import again.")]
+    DiskFull(String),
     #[error("No data directory could be found.")]
     NoDataDirectory,
     #[error("No language pair found in dict.cc file.")]
     NoLanguagePair,
     #[error("Invalid language pair in dict.cc file.")]
     InvalidLanguagePair,
-    #[error("Source language {0} not available. Available are: {1}")]
-    SearchLanguageNotAvailable(String, String),
+    #[error("The language pair {0} has not been imported.")]
+    NotImported(String),
+    #[error("The database {0} is corrupted, import it again.")]
+    CorruptDatabase(String),
+    #[error(
+        "The database {0} does not match its language pair, field {1} differs. Was it renamed? Otherwise import it again."
+    )]
+    SchemaMismatch(String, String),
+    #[error("The database is being written by another import, only one import can run at a time.")]
+    DatabaseLocked,
+    #[error("The database {0} has no index for {1}, import it again.")]
+    MissingIndex(String, &'static str),
+    #[error("The database {0} is split into n-grams with --tokenizer ngram, search it without fuzzy distance.")]
+    FuzzyNgramSearch(String),
+    #[error("Not a dict.cc database: {0}")]
+    NotDatabase(String),
+    #[error("The databases have different language pairs: {0} and {1}")]
+    DifferentLanguagePairs(String, String),
+    #[error("The file is in the {1} format, not {0}.")]
+    ImportFormatMismatch(&'static str, &'static str),
+    #[error("No entries found in dict.cc file {0}, it might be empty or truncated.")]
+    EmptyDictionary(String),
+    #[error(
+        "Source language {0} not available. Available are: {}",
+        .1.iter().map(|code| match language::name(code) {
+            Some(name) => format!("{} ({})", style::bold_err(code), name),
+            None => style::bold_err(code),
+        }).join(", ")
+    )]
+    SearchLanguageNotAvailable(String, Vec<String>),
+    #[error("The import was interrupted, the partial database has been removed and the old one is unchanged.")]
+    ImportInterrupted,
+    #[error("Invalid alias in {0}, line {1}. Aliases are written as name = arguments, e.g. g = -l de-en -f en")]
+    InvalidAlias(String, usize),
+    #[error("The alias {0} has the name of a subcommand, rename it in {1}.")]
+    AliasCollision(String, String),
+    #[error("No text-to-speech program found, i.e. say on macOS, PowerShell on Windows or else spd-say, espeak-ng or espeak.")]
+    NoSpeechProgram,
+    #[error("The text-to-speech program {0} failed.")]
+    SpeechFailed(String),
+    #[error("No translation found.")]
+    NoResults,
+    #[error("No {0} given and no default available.")]
+    MissingArgument(&'static str),
+    #[error("Invalid regex: {0}")]
+    InvalidRegex(#[from] regex::Error),
     #[error("Parse error: {0}")]
-    ParseError(#[from] pest::error::Error<parser::Rule>),
+    ParseError(#[from] Box<pest::error::Error<parser::Rule>>),
     #[error("Database error: {0}")]
     TantivyError(#[from] tantivy::TantivyError),
     #[error("CSV error: {0}")]
     CsvError(#[from] csv::Error),
 }
+
+impl DictCliError {
+    /// The stable name of the error, see `--error-format json`
+    pub(crate) fn code(&self) -> &'static str {
+        match self {
+            DictCliError::IoError(_) => "IoError",
+            DictCliError::NotDirectory(_) => "NotDirectory",
+            DictCliError::AlreadyImported => "AlreadyImported",
+            DictCliError::DiskFull(_) => "DiskFull",
+            DictCliError::NoDataDirectory => "NoDataDirectory",
+            DictCliError::NoLanguagePair => "NoLanguagePair",
+            DictCliError::InvalidLanguagePair => "InvalidLanguagePair",
+            DictCliError::NotImported(_) => "NotImported",
+            DictCliError::NotDatabase(_) => "NotDatabase",
+            DictCliError::CorruptDatabase(_) => "CorruptDatabase",
+            DictCliError::SchemaMismatch(_, _) => "SchemaMismatch",
+            DictCliError::DatabaseLocked => "DatabaseLocked",
+            DictCliError::MissingIndex(_, _) => "MissingIndex",
+            DictCliError::FuzzyNgramSearch(_) => "FuzzyNgramSearch",
+            DictCliError::DifferentLanguagePairs(_, _) => "DifferentLanguagePairs",
+            DictCliError::ImportFormatMismatch(_, _) => "ImportFormatMismatch",
+            DictCliError::EmptyDictionary(_) => "EmptyDictionary",
+            DictCliError::SearchLanguageNotAvailable(_, _) => "SearchLanguageNotAvailable",
+            DictCliError::ImportInterrupted => "ImportInterrupted",
+            DictCliError::InvalidAlias(_, _) => "InvalidAlias",
+            DictCliError::AliasCollision(_, _) => "AliasCollision",
+            DictCliError::NoSpeechProgram => "NoSpeechProgram",
+            DictCliError::SpeechFailed(_) => "SpeechFailed",
+            DictCliError::NoResults => "NoResults",
+            DictCliError::MissingArgument(_) => "MissingArgument",
+            DictCliError::InvalidRegex(_) => "InvalidRegex",
+            DictCliError::ParseError(_) => "ParseError",
+            DictCliError::TantivyError(_) => "DatabaseError",
+            DictCliError::CsvError(_) => "CsvError",
+        }
+    }
+
+    /// The exit code of the process, which is the same for errors of one category
+    pub(crate) fn exit_code(&self) -> i32 {
+        match self {
+            // Invalid arguments, clap uses 2 as well
+            DictCliError::MissingArgument(_)
+            | DictCliError::InvalidRegex(_)
+            | DictCliError::FuzzyNgramSearch(_)
+            | DictCliError::SearchLanguageNotAvailable(_, _)
+            | DictCliError::DifferentLanguagePairs(_, _)
+            | DictCliError::InvalidAlias(_, _)
+            | DictCliError::AliasCollision(_, _) => 2,
+            DictCliError::NotImported(_) | DictCliError::NotDatabase(_) | DictCliError::MissingIndex(_, _) => 3,
+            DictCliError::NoResults => 4,
+            // Like a process ended by SIGINT
+            DictCliError::ImportInterrupted => 130,
+            DictCliError::IoError(_)
+            | DictCliError::NotDirectory(_)
+            | DictCliError::DiskFull(_)
+            | DictCliError::NoDataDirectory
+            | DictCliError::NoSpeechProgram
+            | DictCliError::SpeechFailed(_) => 5,
+            // The dict.cc file cannot be imported
+            DictCliError::AlreadyImported
+            | DictCliError::NoLanguagePair
+            | DictCliError::InvalidLanguagePair
+            | DictCliError::ImportFormatMismatch(_, _)
+            | DictCliError::EmptyDictionary(_)
+            | DictCliError::ParseError(_)
+            | DictCliError::CsvError(_) => 6,
+            DictCliError::TantivyError(_)
+            | DictCliError::CorruptDatabase(_)
+            | DictCliError::SchemaMismatch(_, _)
+            | DictCliError::DatabaseLocked => 7,
+        }
+    }
+
+    /// The paths, languages or arguments the error is about
+    fn context(&self) -> serde_json::Value {
+        match self {
+            DictCliError::NotDirectory(path)
+            | DictCliError::DiskFull(path)
+            | DictCliError::NotDatabase(path)
+            | DictCliError::EmptyDictionary(path) => json!({ "path": path }),
+            DictCliError::DifferentLanguagePairs(language_pair_old, language_pair_new) => {
+                json!({ "language_pairs": [language_pair_old, language_pair_new] })
+            }
+            DictCliError::SearchLanguageNotAvailable(language, available) => {
+                json!({ "language": language, "available": available })
+            }
+            DictCliError::NotImported(language_pair)
+            | DictCliError::CorruptDatabase(language_pair)
+            | DictCliError::FuzzyNgramSearch(language_pair) => {
+                json!({ "language_pair": language_pair })
+            }
+            DictCliError::SchemaMismatch(language_pair, field) => {
+                json!({ "language_pair": language_pair, "field": field })
+            }
+            DictCliError::ImportFormatMismatch(format, detected) => json!({ "format": format, "detected": detected }),
+            DictCliError::MissingIndex(language_pair, argument) => {
+                json!({ "language_pair": language_pair, "argument": argument })
+            }
+            DictCliError::MissingArgument(argument) => json!({ "argument": argument }),
+            DictCliError::InvalidAlias(path, line) => json!({ "path": path, "line": line }),
+            DictCliError::AliasCollision(alias, path) => json!({ "alias": alias, "path": path }),
+            DictCliError::SpeechFailed(program) => json!({ "program": program }),
+            _ => json!({}),
+        }
+    }
+}
+
+pub(crate) fn set_json_errors(json_errors: bool) {
+    JSON_ERRORS.store(json_errors, Ordering::Relaxed);
+}
+
+/// Prints the error to stderr, as text or as JSON object with `--error-format json`
+pub(crate) fn print_error(err: &DictCliError) {
+    if JSON_ERRORS.load(Ordering::Relaxed) {
+        eprintln!(
+            "{}",
+            json!({
+                "code": err.code(),
+                // Without the colors of the text output
+                "message": Regex::new("\x1b\\[[0-9;]*m").unwrap().replace_all(&err.to_string(), ""),
+                "context": err.context(),
+            })
+        );
+    } else {
+        eprintln!("Error: {}", err);
+    }
+}
@@ -0,0 +1,68 @@
+use std::fs::OpenOptions;
+use std::io::{ErrorKind, Write};
+use std::path::PathBuf;
+
+use tantivy::schema::Field;
+use tantivy::{doc, Document};
+
+use crate::database::{self, DatabaseSchema};
+use crate::error::DictCliError;
+
+/// Bookmarked entries of a language pair, stored like a dict.cc file without header
+fn favorites_path(language_pair: &str) -> Result<PathBuf, DictCliError> {
+    Ok(database::data_dir()?.join(format!("favorites_{}", language_pair)))
+}
+
+pub(crate) fn add_favorite(
+    language_pair: &str,
+    schema: &DatabaseSchema,
+    document: &Document,
+) -> Result<(), DictCliError> {
+    let fields: [Field; 4] = [
+        schema.lang_left,
+        schema.lang_right,
+        schema.word_classes,
+        schema.subject_labels,
+    ];
+    let line = fields
+        .iter()
+        .map(|field| {
+            document
+                .get_first(*field)
+                .and_then(|value| value.as_text())
+                .unwrap_or_default()
+                .replace(['\t', '\n'], " ")
+        })
+        .collect::<Vec<String>>()
+        .join("\t");
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(favorites_path(language_pair)?)?;
+    writeln!(file, "{}", line)?;
+    Ok(())
+}
+
+pub(crate) fn favorites(language_pair: &str, schema: &DatabaseSchema) -> Result<Vec<Document>, DictCliError> {
+    let content = match std::fs::read_to_string(favorites_path(language_pair)?) {
+        Ok(content) => content,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err.into()),
+    };
+
+    Ok(content
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let mut values = line.split('\t');
+            let mut next_value = || values.next().unwrap_or_default();
+            doc!(
+                schema.lang_left => next_value(),
+                schema.lang_right => next_value(),
+                schema.word_classes => next_value(),
+                schema.subject_labels => next_value(),
+            )
+        })
+        .collect())
+}
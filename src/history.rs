@@ -0,0 +1,156 @@
+use std::fs::OpenOptions;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::database;
+use crate::error::DictCliError;
+
+/// A search of the interactive mode, the time is missing for lines written without one
+pub(crate) struct HistoryEntry {
+    /// Seconds since the Unix epoch
+    pub(crate) time: Option<u64>,
+    pub(crate) query: String,
+}
+
+/// The searches kept per language pair, the oldest ones are removed when the history grows beyond it
+const MAX_HISTORY_ENTRIES: usize = 1000;
+
+/// Searches of the interactive mode of a language pair, one per line with the time separated by a tab
+fn history_path(language_pair: &str) -> Result<PathBuf, DictCliError> {
+    Ok(database::data_dir()?.join(format!("history_{}", language_pair)))
+}
+
+pub(crate) fn append_history(language_pair: &str, query: &str) -> Result<(), DictCliError> {
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+
+    let path = history_path(language_pair)?;
+    let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+    writeln!(file, "{}\t{}", time, query.replace(['\t', '\n'], " "))?;
+    trim_history(&path)
+}
+
+/// Keeps the newest `MAX_HISTORY_ENTRIES` searches. The file is only rewritten once it has a tenth more, so not
+/// after every search of a full history.
+fn trim_history(path: &Path) -> Result<(), DictCliError> {
+    let content = std::fs::read_to_string(path)?;
+    let lines: Vec<&str> = content.lines().collect();
+    if lines.len() <= MAX_HISTORY_ENTRIES + MAX_HISTORY_ENTRIES / 10 {
+        return Ok(());
+    }
+    let mut kept = lines[lines.len() - MAX_HISTORY_ENTRIES..].join("\n");
+    kept.push('\n');
+    // Written next to the history and moved in place, so that a failed write keeps the old history
+    let new_path = path.with_extension("new");
+    std::fs::write(&new_path, kept)?;
+    std::fs::rename(new_path, path)?;
+    Ok(())
+}
+
+/// The searches of a language pair, oldest first
+pub(crate) fn history(language_pair: &str) -> Result<Vec<HistoryEntry>, DictCliError> {
+    let content = match std::fs::read_to_string(history_path(language_pair)?) {
+        Ok(content) => content,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err.into()),
+    };
+
+    Ok(content
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| match line.split_once('\t') {
+            Some((time, query)) if time.parse::<u64>().is_ok() => HistoryEntry {
+                time: time.parse().ok(),
+                query: query.to_string(),
+            },
+            _ => HistoryEntry {
+                time: None,
+                query: line.to_string(),
+            },
+        })
+        .collect())
+}
+
+pub(crate) fn clear_history(language_pair: &str) -> Result<(), DictCliError> {
+    match std::fs::remove_file(history_path(language_pair)?) {
+        Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Formats seconds since the Unix epoch as `YYYY-MM-DD HH:MM` in UTC
+pub(crate) fn format_time(time: u64) -> String {
+    let days = (time / 86400) as i64;
+    let minutes_of_day = time % 86400 / 60;
+
+    // Converts the days to a date of the proleptic Gregorian calendar, with eras of 400 years starting at March 1st
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        year,
+        month,
+        day,
+        minutes_of_day / 60,
+        minutes_of_day % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_times() {
+        assert_eq!(format_time(0), "1970-01-01 00:00");
+        // A leap day, of a year divisible by 4
+        assert_eq!(format_time(1709210096), "2024-02-29 12:34");
+        // The last minute of a century, 2000 is a leap year although divisible by 100
+        assert_eq!(format_time(978307140), "2000-12-31 23:59");
+        // 2100 is no leap year, the day after February 28th is March 1st
+        assert_eq!(format_time(4107542340), "2100-02-28 23:59");
+        assert_eq!(format_time(4107542400), "2100-03-01 00:00");
+    }
+
+    #[test]
+    fn trim_history_to_newest_entries() {
+        let path = std::env::temp_dir().join(format!("dictcc-cli-history-{}", std::process::id()));
+        let lines: String = (0..MAX_HISTORY_ENTRIES + MAX_HISTORY_ENTRIES / 10)
+            .map(|number| format!("{}\tsearch {}\n", number, number))
+            .collect();
+        std::fs::write(&path, &lines).unwrap();
+        trim_history(&path).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), lines);
+
+        std::fs::write(&path, format!("{}0\tnewest\n", lines)).unwrap();
+        trim_history(&path).unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        let kept: Vec<&str> = content.lines().collect();
+        assert_eq!(kept.len(), MAX_HISTORY_ENTRIES);
+        assert_eq!(
+            kept[0],
+            format!(
+                "{}\tsearch {}",
+                MAX_HISTORY_ENTRIES / 10 + 1,
+                MAX_HISTORY_ENTRIES / 10 + 1
+            )
+        );
+        assert_eq!(kept[MAX_HISTORY_ENTRIES - 1], "0\tnewest");
+        std::fs::remove_file(&path).unwrap();
+    }
+}
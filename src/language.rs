@@ -0,0 +1,88 @@
+/// Languages offered by dict.cc: (code, ISO 639-2 codes, English name)
+const LANGUAGES: [(&str, &[&str], &str); 27] = [
+    ("bg", &["bul"], "bulgarian"),
+    ("bs", &["bos"], "bosnian"),
+    ("cs", &["ces", "cze"], "czech"),
+    ("da", &["dan"], "danish"),
+    ("de", &["deu", "ger"], "german"),
+    ("el", &["ell", "gre"], "greek"),
+    ("en", &["eng"], "english"),
+    ("eo", &["epo"], "esperanto"),
+    ("es", &["spa"], "spanish"),
+    ("fi", &["fin"], "finnish"),
+    ("fr", &["fra", "fre"], "french"),
+    ("hr", &["hrv"], "croatian"),
+    ("hu", &["hun"], "hungarian"),
+    ("is", &["isl", "ice"], "icelandic"),
+    ("it", &["ita"], "italian"),
+    ("la", &["lat"], "latin"),
+    ("nl", &["nld", "dut"], "dutch"),
+    ("no", &["nor"], "norwegian"),
+    ("pl", &["pol"], "polish"),
+    ("pt", &["por"], "portuguese"),
+    ("ro", &["ron", "rum"], "romanian"),
+    ("ru", &["rus"], "russian"),
+    ("sk", &["slk", "slo"], "slovak"),
+    ("sq", &["sqi", "alb"], "albanian"),
+    ("sr", &["srp"], "serbian"),
+    ("sv", &["swe"], "swedish"),
+    ("tr", &["tur"], "turkish"),
+];
+
+/// Resolves a language code, three-letter code, regional code or English name
+/// to its dict.cc code, e.g. `eng`, `en-US` and `English` all resolve to `en`
+pub(crate) fn resolve(language: &str) -> String {
+    let language = language.trim().to_lowercase();
+    let primary_subtag = language.split(['-', '_']).next().unwrap_or_default();
+
+    LANGUAGES
+        .iter()
+        .find(|(code, iso_codes, name)| {
+            *code == primary_subtag || iso_codes.contains(&primary_subtag) || *name == language
+        })
+        .map(|(code, _, _)| code.to_string())
+        .unwrap_or(language)
+}
+
+/// Aliases that resolve to the given dict.cc code
+pub(crate) fn aliases(code: &str) -> Vec<&'static str> {
+    LANGUAGES
+        .iter()
+        .find(|(language_code, _, _)| *language_code == code)
+        .map(|(_, iso_codes, name)| iso_codes.iter().copied().chain(std::iter::once(*name)).collect())
+        .unwrap_or_default()
+}
+
+/// The English name of the language, if known
+pub(crate) fn name(code: &str) -> Option<&'static str> {
+    LANGUAGES
+        .iter()
+        .find(|(language_code, _, _)| *language_code == code)
+        .map(|(_, _, name)| *name)
+}
+
+/// Lowercases with the rules of the language where they differ from the default Unicode casing:
+/// the dotted and dotless i of Turkish, and the German ß, which is folded to ss so that `STRASSE` finds `Straße`
+pub(crate) fn lowercase(code: &str, text: &str) -> String {
+    match code {
+        "tr" => text.replace('I', "ı").replace('İ', "i").to_lowercase(),
+        "de" => text.to_lowercase().replace('ß', "ss"),
+        _ => text.to_lowercase(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lowercase_with_language_rules() {
+        // The default casing keeps the dot of İ as a combining character
+        assert_eq!(lowercase("en", "İstanbul"), "i\u{307}stanbul");
+        assert_eq!(lowercase("tr", "İstanbul"), "istanbul");
+        assert_eq!(lowercase("tr", "ISPARTA"), "ısparta");
+        assert_eq!(lowercase("de", "STRASSE"), "strasse");
+        assert_eq!(lowercase("de", "straße"), "strasse");
+        assert_eq!(lowercase("en", "straße"), "straße");
+    }
+}
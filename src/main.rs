@@ -1,98 +1,470 @@
 #[macro_use]
 extern crate pest_derive;
 
+mod alias;
 mod cli;
 mod database;
+mod directory;
 mod error;
+mod favorites;
+mod history;
+mod language;
 mod parser;
+mod query;
+mod result;
+mod speech;
+mod state;
+mod style;
+mod template;
+mod tui;
 
+use std::cell::{Cell, RefCell};
 use std::cmp::Reverse;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+use std::io::{BufRead, ErrorKind, Write};
 
-use cli::Settings;
-use comfy_table::presets::{ASCII_FULL, UTF8_FULL};
-use comfy_table::{ContentArrangement, Table};
-use database::DatabaseSearch;
+use cli::{OutputFormat, Rank, Settings, TableColumn};
+use comfy_table::presets::{ASCII_NO_BORDERS, UTF8_FULL};
+use comfy_table::{CellAlignment, ContentArrangement, Table};
+use database::{DatabaseSearch, FuzzyMode, ImportSummary, LabelField, SearchOptions, SearchResults, WordMatch};
 use error::DictCliError;
+use itertools::Itertools;
+use query::SearchExpression;
+use regex::Regex;
+use result::SearchResult;
 use rustyline::completion::Completer;
-use rustyline::config::BellStyle;
 use rustyline::error::ReadlineError;
 use rustyline::highlight::Highlighter;
 use rustyline::hint::Hinter;
 use rustyline::validate::Validator;
 use rustyline::{Config, Editor, Helper};
-use tantivy::schema::Field;
+use serde_json::json;
+use tantivy::schema::{Field, Value};
 use tantivy::Document;
+use template::{Placeholder, Template};
 use unicode_normalization::UnicodeNormalization;
 
-fn main() -> Result<(), DictCliError> {
+/// Most results printed as a table or template to a terminal without --limit-results, as huge tables freeze it
+const TERMINAL_RESULT_CAP: usize = 500;
+
+/// Table width if the terminal reports a width of zero and `COLUMNS` is not set, e.g. in some CI environments
+const FALLBACK_TABLE_WIDTH: u16 = 80;
+
+fn main() {
+    if let Err(err) = run() {
+        error::print_error(&err);
+        std::process::exit(err.exit_code());
+    }
+}
+
+fn run() -> Result<(), DictCliError> {
     match cli::parse_settings()? {
-        Settings::Import { file, force } => {
-            database::import_dictcc_file(file, force)?;
+        Settings::Import { file, options } => {
+            let summary_count = options.summary;
+            let summary = database::import_dictcc_file(file, options)?;
+            if let Some(count) = summary_count {
+                print_label_summary(&summary, count);
+            }
         }
         Settings::Delete { language_pair } => {
             database::remove_database(&language_pair)?;
         }
-        Settings::Translate {
+        Settings::List { json } => {
+            print_language_pairs(json)?;
+        }
+        Settings::ExportWords {
             language_pair,
             language_from,
+        } => {
+            let db_search = database::DatabaseSearch::new(&language_pair)?;
+            let reverse_langs = db_search.is_reverse_langs(&language_from)?;
+            let mut stdout_lock = std::io::stdout().lock();
+            for word in db_search.words(reverse_langs)? {
+                match writeln!(stdout_lock, "{}", word) {
+                    // The reader stopped early, e.g. head
+                    Err(err) if err.kind() == ErrorKind::BrokenPipe => break,
+                    result => result?,
+                }
+            }
+        }
+        Settings::Favorites {
+            language_pair,
+            output_format,
+        } => {
+            print_favorites(&language_pair, output_format)?;
+        }
+        Settings::History {
+            language_pair,
+            limit,
+            clear,
+        } => {
+            let language_pair = database::DatabaseSearch::new(&language_pair)?.language_pair();
+            if clear {
+                history::clear_history(&language_pair)?;
+            } else {
+                print_history(&language_pair, limit)?;
+            }
+        }
+        Settings::Warmup { language_pair } => {
+            let start = std::time::Instant::now();
+            // Opening the database checks that it is one and loads its segments
+            let db_search = database::DatabaseSearch::new(&language_pair)?;
+            let bytes = database::warmup_database(&db_search.language_pair())?;
+            println!(
+                "Read {} of {} in {:.2} s.",
+                format_bytes(bytes),
+                db_search.language_pair(),
+                start.elapsed().as_secs_f64()
+            );
+        }
+        Settings::Verify { language_pair } => {
+            let db_search = database::DatabaseSearch::new(&language_pair)?;
+            let report = db_search.verify()?;
+            println!(
+                "Checked {} entries: {} unreadable, {} incomplete, {} corrupted files.",
+                report.documents,
+                report.unreadable_documents,
+                report.incomplete_documents,
+                report.corrupted_files.len()
+            );
+            for corrupted_file in report.corrupted_files.iter() {
+                println!("Corrupted: {}", corrupted_file.display());
+            }
+            if !report.is_ok() {
+                return Err(DictCliError::CorruptDatabase(db_search.language_pair()));
+            }
+        }
+        Settings::Diff {
+            database_old,
+            database_new,
+            output_format,
+        } => {
+            print_diff(&database_old, &database_new, output_format)?;
+        }
+        Settings::SearchAll {
+            query,
+            fuzzy_distance,
+            output_format,
+        } => {
+            print_search_all(&query, fuzzy_distance, output_format)?;
+        }
+        Settings::Translate {
+            language_pairs,
+            language_from,
             fuzzy_distance,
+            max_results_before_tighten,
+            max_fuzzy_expansions,
+            autocorrect,
+            fuzzy_mode,
             limit_results,
+            skip_results,
+            tail_results,
+            search_memory_budget,
+            search_extra,
+            include_reverse,
             minimum_similarity,
+            min_word_length,
+            rank,
+            no_sort,
+            explain,
+            print_query,
+            dump,
+            output_format,
+            swap_columns,
+            column_order,
+            align,
+            show_source,
+            target_only,
+            show_extra,
+            show_phonetic,
+            highlight,
+            separate_exact,
+            footer,
+            literal_tokens,
+            case_sensitive,
+            contains,
+            exact_extra,
             completion_type,
-            ascii,
+            bell_style,
+            tab_stop,
+            table_preset,
+            force_table,
+            width,
+            tui,
+            select,
+            exit_on_empty,
+            speak,
+            stdin_queries,
+            random,
+            regex_full,
+            search_field,
+            back_translate,
             search,
         } => {
-            let db_search = database::DatabaseSearch::new(&language_pair)?;
-            let reverse_langs = db_search.is_reverse_langs(&language_from)?;
-            let source_lang_upper = language_from.to_uppercase();
-            let target_lang_upper = db_search.target_language(&language_from)?.to_uppercase();
+            let db_searches = language_pairs
+                .iter()
+                .map(|language_pair| database::DatabaseSearch::new(language_pair))
+                .collect::<Result<Vec<_>, _>>()?;
 
-            let (source_field, target_field) = if !reverse_langs {
-                (&db_search.schema.lang_left, &db_search.schema.lang_right)
-            } else {
-                (&db_search.schema.lang_right, &db_search.schema.lang_left)
-            };
+            let search_translations = db_searches
+                .iter()
+                .map(|db_search| {
+                    let reverse_langs = db_search.is_reverse_langs(&language_from)?;
+                    let (source_field, target_field, target_frequency_field) = if !reverse_langs {
+                        (
+                            &db_search.schema.lang_left,
+                            &db_search.schema.lang_right,
+                            &db_search.schema.frequency_lang_right,
+                        )
+                    } else {
+                        (
+                            &db_search.schema.lang_right,
+                            &db_search.schema.lang_left,
+                            &db_search.schema.frequency_lang_left,
+                        )
+                    };
 
-            let search_translations = SearchTranslations {
-                db_search: &db_search,
-                source_field,
-                target_field,
-                reverse_langs,
-                fuzzy_distance,
-                limit_results,
-                minimum_similarity,
-                ascii,
-                source_lang_upper,
-                target_lang_upper,
-            };
+                    Ok(SearchTranslations {
+                        db_search,
+                        source_field,
+                        target_field,
+                        target_frequency_field,
+                        reverse_langs,
+                        fuzzy_distance: Cell::new(fuzzy_distance),
+                        max_results_before_tighten,
+                        max_fuzzy_expansions,
+                        autocorrect,
+                        fuzzy_mode,
+                        limit_results: Cell::new(limit_results),
+                        skip_results,
+                        tail_results,
+                        result_cap: if limit_results.is_none()
+                            && tail_results.is_none()
+                            && atty::is(atty::Stream::Stdout)
+                        {
+                            Some(TERMINAL_RESULT_CAP)
+                        } else {
+                            None
+                        },
+                        search_memory_budget,
+                        search_extra,
+                        include_reverse,
+                        minimum_similarity,
+                        min_word_length,
+                        rank,
+                        no_sort,
+                        explain,
+                        print_query,
+                        dump,
+                        output_format: output_format.clone(),
+                        swap_columns,
+                        column_order: column_order.clone(),
+                        align,
+                        show_source,
+                        target_only,
+                        show_extra,
+                        show_phonetic,
+                        highlight,
+                        separate_exact,
+                        footer,
+                        literal_tokens,
+                        case_sensitive,
+                        contains,
+                        exact_extra,
+                        table_preset: default_table_preset(table_preset, force_table),
+                        width,
+                        regex_full,
+                        search_field,
+                        back_translate,
+                        back_translations: RefCell::default(),
+                        subject_scope: RefCell::default(),
+                        source_lang_upper: language_from.to_uppercase(),
+                        target_lang_upper: db_search.target_language(&language_from)?.to_uppercase(),
+                    })
+                })
+                .collect::<Result<Vec<_>, DictCliError>>()?;
+
+            // Only remember the selection once the source language is known to exist in every language pair,
+            // so that an invalid default never reaches the interactive mode
+            if let Err(err) = state::store_last_selection(&language_pairs[0], &language_from) {
+                eprintln!("Could not store the language selection: {}", err);
+            }
 
             if let Some(search) = search {
-                search_translations.print_results(&search);
+                if select && atty::is(atty::Stream::Stdout) {
+                    return select_translation(&search_translations, &search, speak);
+                }
+
+                let results = print_all_results(&search_translations, &search)?;
+                if speak {
+                    match results.first() {
+                        Some((search_translations, document)) => speak_translation(search_translations, document),
+                        None => eprintln!("Nothing to speak, the search found no results."),
+                    }
+                }
+                if results.is_empty() && (exit_on_empty || matches!(output_format, OutputFormat::First)) {
+                    return Err(DictCliError::NoResults);
+                }
+                return Ok(());
+            }
+
+            if let Some(count) = random {
+                for search_translations in with_sections(&search_translations) {
+                    search_translations.print_random(count as usize)?;
+                }
                 return Ok(());
             }
 
+            if stdin_queries {
+                // Results are flushed per line, as the other end may wait for them before sending the next query
+                let mut stdout = std::io::stdout();
+                for line in std::io::stdin().lock().lines() {
+                    // A failed query does not end the others, its error is printed like with --error-format
+                    if let Err(err) = print_all_results(&search_translations, &line?) {
+                        error::print_error(&err);
+                    }
+                    stdout.flush()?;
+                }
+                return Ok(());
+            }
+
+            if tui {
+                if search_translations.len() > 1 {
+                    eprintln!("The full-screen mode only searches {}.", language_pairs[0]);
+                }
+                return tui::run(&search_translations[0]);
+            }
+
             let mut readline_editor = Editor::<TabCompletion>::with_config(
                 Config::builder()
                     .completion_type(completion_type)
-                    .bell_style(BellStyle::None)
-                    .tab_stop(4)
+                    .bell_style(bell_style)
+                    .tab_stop(tab_stop)
                     .indent_size(4)
                     .build(),
             )
             .unwrap();
             readline_editor.set_helper(Some(TabCompletion {
-                db_search: &db_search,
-                reverse_langs,
+                db_search: search_translations[0].db_search,
+                reverse_langs: search_translations[0].reverse_langs,
+                last_completions: RefCell::default(),
             }));
 
+            let history_language_pair = search_translations[0].db_search.language_pair();
+            match history::history(&history_language_pair) {
+                Ok(entries) => {
+                    for entry in entries {
+                        readline_editor.add_history_entry(entry.query);
+                    }
+                }
+                Err(err) => eprintln!("Could not load the search history: {}", err),
+            }
+
+            let mut last_results = Vec::new();
+            // The last search and its fuzzy distance suffix, for :again
+            let mut last_query: Option<(String, Option<u8>)> = None;
+
             loop {
-                let readline = readline_editor.readline("> ");
+                let prompt = match search_translations[0].subject_scope.borrow().as_deref() {
+                    Some(subject) => format!("[{}] > ", subject),
+                    None => "> ".to_string(),
+                };
+                let readline = readline_editor.readline(&prompt);
 
                 match readline {
                     Ok(line) => {
-                        readline_editor.add_history_entry(&line);
-                        search_translations.print_results(&line);
+                        // Commands are kept in the history of the session, but only searches are stored
+                        if readline_editor.add_history_entry(&line) && !line.trim_start().starts_with(':') {
+                            if let Err(err) = history::append_history(&history_language_pair, &line) {
+                                eprintln!("Could not store the search history: {}", err);
+                            }
+                        }
+
+                        if let Some(number) = line
+                            .trim()
+                            .strip_prefix(":fav")
+                            .filter(|number| number.is_empty() || number.starts_with(' '))
+                        {
+                            add_favorite(&last_results, number);
+                            continue;
+                        }
+
+                        if let Some(number) = line.trim().strip_prefix(":speak ") {
+                            speak_result(&last_results, number);
+                            continue;
+                        }
+
+                        if let Some(subject) = line
+                            .trim()
+                            .strip_prefix(":subject")
+                            .filter(|subject| subject.is_empty() || subject.starts_with(' '))
+                        {
+                            set_subject_scope(&search_translations, subject);
+                            continue;
+                        }
+
+                        if line.trim() == ":reload" {
+                            for search_translations in search_translations.iter() {
+                                if let Err(err) = search_translations.db_search.reload() {
+                                    eprintln!("Could not reload the database: {}", err);
+                                }
+                            }
+                            if let Some(tab_completion) = readline_editor.helper() {
+                                tab_completion.last_completions.take();
+                            }
+                            continue;
+                        }
+
+                        if let Some(args) = line
+                            .trim()
+                            .strip_prefix(":again")
+                            .filter(|args| args.is_empty() || args.starts_with(' '))
+                        {
+                            let (query, query_fuzzy_distance) = match last_query.as_ref() {
+                                Some(last_query) => last_query,
+                                None => {
+                                    eprintln!("No search to repeat yet.");
+                                    continue;
+                                }
+                            };
+                            let (again_fuzzy_distance, again_limit_results) = match again_overrides(args) {
+                                Ok(overrides) => overrides,
+                                Err(arg) => {
+                                    eprintln!("Unknown argument {} of :again, e.g. :again ~2 or :again -r 5.", arg);
+                                    continue;
+                                }
+                            };
+                            for search_translations in search_translations.iter() {
+                                search_translations
+                                    .fuzzy_distance
+                                    .set(again_fuzzy_distance.or(*query_fuzzy_distance).unwrap_or(fuzzy_distance));
+                                search_translations
+                                    .limit_results
+                                    .set(again_limit_results.or(limit_results));
+                            }
+                            last_results = print_all_results_or_error(&search_translations, query);
+                            for search_translations in search_translations.iter() {
+                                search_translations.limit_results.set(limit_results);
+                            }
+                            continue;
+                        }
+
+                        let (line, query_fuzzy_distance) = match query::fuzzy_distance_suffix(&line) {
+                            Ok(suffix) => suffix,
+                            Err(err) => {
+                                eprintln!("{}", err);
+                                continue;
+                            }
+                        };
+                        for search_translations in search_translations.iter() {
+                            search_translations
+                                .fuzzy_distance
+                                .set(query_fuzzy_distance.unwrap_or(fuzzy_distance));
+                        }
+
+                        last_results = print_all_results_or_error(&search_translations, line);
+                        last_query = Some((line.to_owned(), query_fuzzy_distance));
                     }
                     Err(ReadlineError::Interrupted) => {
                         continue;
@@ -100,6 +472,9 @@ fn main() -> Result<(), DictCliError> {
                     Err(ReadlineError::Eof) => {
                         break;
                     }
+                    Err(err) if is_recoverable_readline_error(&err) => {
+                        continue;
+                    }
                     Err(err) => {
                         eprintln!("Readline error: {}", err);
                         break;
@@ -112,106 +487,1604 @@ fn main() -> Result<(), DictCliError> {
     Ok(())
 }
 
+/// Whether the interactive mode can go on after the error, e.g. if a signal like a window resize interrupted reading
+fn is_recoverable_readline_error(err: &ReadlineError) -> bool {
+    match err {
+        ReadlineError::WindowResized => true,
+        ReadlineError::Io(io_err) => matches!(io_err.kind(), ErrorKind::Interrupted | ErrorKind::WouldBlock),
+        #[cfg(unix)]
+        ReadlineError::Errno(errno) => std::io::Error::from(*errno).kind() == ErrorKind::Interrupted,
+        _ => false,
+    }
+}
+
+/// Prints a heading per target language before its results, if several language pairs are searched
+fn with_sections<'a, 'b>(
+    search_translations: &'b [SearchTranslations<'a>],
+) -> impl Iterator<Item = &'b SearchTranslations<'a>> {
+    let sections = search_translations.len() > 1;
+    search_translations.iter().inspect(move |search_translations| {
+        if sections && matches!(search_translations.output_format, OutputFormat::Table) {
+            println!(
+                "{}",
+                style::bold(&format!(
+                    "{} → {}",
+                    search_translations.source_lang_upper, search_translations.target_lang_upper
+                ))
+            );
+        }
+    })
+}
+
+/// Searches every language pair and returns the printed results with their search.
+/// A failed search ends the search of the remaining language pairs.
+fn print_all_results<'a, 'b>(
+    search_translations: &'b [SearchTranslations<'a>],
+    line: &str,
+) -> Result<Vec<(&'b SearchTranslations<'a>, Document)>, DictCliError> {
+    let mut results = Vec::new();
+    for search_translations in with_sections(search_translations) {
+        let documents = search_translations.print_results(line)?;
+        results.extend(documents.into_iter().map(|document| (search_translations, document)));
+    }
+    Ok(results)
+}
+
+/// Like `print_all_results` for the interactive mode, which prints the error and goes on with the next search
+fn print_all_results_or_error<'a, 'b>(
+    search_translations: &'b [SearchTranslations<'a>],
+    line: &str,
+) -> Vec<(&'b SearchTranslations<'a>, Document)> {
+    print_all_results(search_translations, line).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        Vec::new()
+    })
+}
+
+/// Lets the user pick one of the results of every language pair from a numbered menu and prints its target
+fn select_translation(search_translations: &[SearchTranslations], line: &str, speak: bool) -> Result<(), DictCliError> {
+    let mut translations = Vec::new();
+    for search_translations in search_translations {
+        translations.extend(
+            search_translations
+                .translations(line)?
+                .into_iter()
+                .map(|translation| (search_translations, translation)),
+        );
+    }
+    if translations.is_empty() {
+        return Err(DictCliError::NoResults);
+    }
+
+    for (number, (_, translation)) in translations.iter().enumerate() {
+        eprintln!("{:>3}) {} → {}", number + 1, translation.source, translation.target);
+    }
+
+    let mut input = String::new();
+    loop {
+        eprint!("Select 1-{}: ", translations.len());
+        input.clear();
+        if std::io::stdin().read_line(&mut input)? == 0 {
+            // Closed input, nothing selected
+            eprintln!();
+            return Ok(());
+        }
+
+        match input.trim().parse::<usize>() {
+            Ok(number) if (1..=translations.len()).contains(&number) => {
+                let (search_translations, translation) = &translations[number - 1];
+                println!("{}", translation.target);
+                if speak {
+                    if let Err(err) = speech::speak(&translation.target, search_translations.target_language()) {
+                        eprintln!("Could not speak the translation: {}", err);
+                    }
+                }
+                return Ok(());
+            }
+            _ => eprintln!("No result {}.", input.trim()),
+        }
+    }
+}
+
+/// Limits the following searches to a subject label like `med.` or `[med.]`, `off` searches all entries again
+fn set_subject_scope(search_translations: &[SearchTranslations], subject: &str) {
+    let subject = subject.trim().trim_start_matches('[').trim_end_matches(']').trim();
+    let subject_scope = match subject {
+        "" => {
+            match search_translations[0].subject_scope.borrow().as_deref() {
+                Some(subject) => println!("Searching entries with [{}].", subject),
+                None => println!("Searching all entries, limit them with :subject med."),
+            }
+            return;
+        }
+        "off" => None,
+        subject => Some(subject.to_lowercase()),
+    };
+    for search_translations in search_translations {
+        *search_translations.subject_scope.borrow_mut() = subject_scope.clone();
+    }
+}
+
+/// The fuzzy distance like `~2` and the limit like `-r 5` of `:again`, or the first argument that is neither
+fn again_overrides(args: &str) -> Result<(Option<u8>, Option<u32>), String> {
+    let mut fuzzy_distance = None;
+    let mut limit_results = None;
+    let mut args = args.split_whitespace();
+
+    while let Some(arg) = args.next() {
+        if let Some(distance) = arg.strip_prefix('~') {
+            fuzzy_distance = match distance.parse() {
+                Ok(distance) if distance <= database::MAX_FUZZY_DISTANCE => Some(distance),
+                _ => return Err(arg.to_owned()),
+            };
+        } else if arg == "-r" || arg == "--limit-results" {
+            limit_results = match args.next().map(str::parse::<u32>) {
+                Some(Ok(limit)) if limit > 0 => Some(limit),
+                _ => return Err(arg.to_owned()),
+            };
+        } else {
+            return Err(arg.to_owned());
+        }
+    }
+
+    Ok((fuzzy_distance, limit_results))
+}
+
+/// Adds the result with the given number, counted from 1, of the last search to the favorites
+fn add_favorite(last_results: &[(&SearchTranslations, Document)], number: &str) {
+    if number.trim().is_empty() {
+        eprintln!("Usage: :fav <number>, e.g. :fav 1 for the first result of the last search.");
+        return;
+    }
+
+    let result = match number.trim().parse::<usize>() {
+        Ok(number) if number >= 1 => last_results.get(number - 1),
+        _ => None,
+    };
+
+    let (search_translations, document) = match result {
+        Some(result) => result,
+        None => {
+            eprintln!("No result {} in the last search.", number.trim());
+            return;
+        }
+    };
+
+    let db_search = search_translations.db_search;
+    match favorites::add_favorite(&db_search.language_pair(), &db_search.schema, document) {
+        Ok(()) => println!("Added to favorites."),
+        Err(err) => eprintln!("Could not add the favorite: {}", err),
+    }
+}
+
+/// Reads the target of the result with the given number, counted from 1, of the last search aloud
+fn speak_result(last_results: &[(&SearchTranslations, Document)], number: &str) {
+    let result = match number.trim().parse::<usize>() {
+        Ok(number) if number >= 1 => last_results.get(number - 1),
+        _ => None,
+    };
+
+    match result {
+        Some((search_translations, document)) => speak_translation(search_translations, document),
+        None => eprintln!("No result {} in the last search.", number.trim()),
+    }
+}
+
+/// Speaks the target of the result in its language
+fn speak_translation(search_translations: &SearchTranslations, document: &Document) {
+    let target = document
+        .get_first(*search_translations.target_field)
+        .and_then(|value| value.as_text())
+        .unwrap_or_default();
+    if let Err(err) = speech::speak(target, search_translations.target_language()) {
+        eprintln!("Could not speak the translation: {}", err);
+    }
+}
+
+fn print_favorites(language_pair: &str, output_format: OutputFormat) -> Result<(), DictCliError> {
+    let db_search = database::DatabaseSearch::new(language_pair)?;
+    let documents = favorites::favorites(&db_search.language_pair(), &db_search.schema)?;
+    listing_translations(&db_search, output_format).print_sorted_documents("", &unsorted_documents(&documents));
+    Ok(())
+}
+
+/// Prints the most recent searches last, with the time they were made if it is known
+fn print_history(language_pair: &str, limit: Option<u32>) -> Result<(), DictCliError> {
+    let entries = history::history(language_pair)?;
+    let skipped = limit.map_or(0, |limit| entries.len().saturating_sub(limit as usize));
+    let mut stdout_lock = std::io::stdout().lock();
+    for entry in entries.iter().skip(skipped) {
+        match entry.time {
+            Some(time) => writeln!(stdout_lock, "{}  {}", history::format_time(time), entry.query)?,
+            None => writeln!(stdout_lock, "{}", entry.query)?,
+        }
+    }
+    Ok(())
+}
+
+/// Prints the entries only in the new database as added and the entries only in the old database as removed,
+/// entries are compared by their source and target
+fn print_diff(database_old: &str, database_new: &str, output_format: OutputFormat) -> Result<(), DictCliError> {
+    let db_old = open_database(database_old)?;
+    let db_new = open_database(database_new)?;
+    if db_old.language_pair() != db_new.language_pair() {
+        return Err(DictCliError::DifferentLanguagePairs(
+            db_old.language_pair(),
+            db_new.language_pair(),
+        ));
+    }
+
+    let schema = &db_old.schema;
+    let documents_old = db_old.all_documents()?;
+    let documents_new = db_new.all_documents()?;
+    let entry_set = |documents: &[Document]| -> HashSet<(String, String)> {
+        unsorted_documents(documents)
+            .into_iter()
+            .map(|sorted_doc| diff_key(schema, &sorted_doc))
+            .collect()
+    };
+    let entries_old = entry_set(&documents_old);
+    let entries_new = entry_set(&documents_new);
+
+    let mut changes: Vec<SortedDocument> = unsorted_documents(&documents_old)
+        .into_iter()
+        .filter(|sorted_doc| !entries_new.contains(&diff_key(schema, sorted_doc)))
+        .map(|sorted_doc| SortedDocument {
+            diff_status: Some(DiffStatus::Removed),
+            ..sorted_doc
+        })
+        .collect();
+    changes.extend(
+        unsorted_documents(&documents_new)
+            .into_iter()
+            .filter(|sorted_doc| !entries_old.contains(&diff_key(schema, sorted_doc)))
+            .map(|sorted_doc| SortedDocument {
+                diff_status: Some(DiffStatus::Added),
+                ..sorted_doc
+            }),
+    );
+
+    listing_translations(&db_old, output_format).print_sorted_documents("", &changes);
+    Ok(())
+}
+
+/// Searches every imported language pair in both directions, the results are labeled with their direction
+fn print_search_all(query: &str, fuzzy_distance: u8, output_format: OutputFormat) -> Result<(), DictCliError> {
+    let mut language_pairs = database::available_language_pairs().unwrap_or_default().into_vec();
+    language_pairs.sort_unstable();
+
+    let mut directions: Vec<DirectionResults> = Vec::new();
+    for language_pair in language_pairs.iter() {
+        let db_search = DatabaseSearch::new(language_pair)?;
+        for reverse_langs in [false, true] {
+            let search_translations = direction_translations(&db_search, reverse_langs, output_format.clone());
+            search_translations.fuzzy_distance.set(fuzzy_distance);
+            let translations = search_translations.translations(query)?;
+            if !translations.is_empty() {
+                let (language_from, language_to) = if !reverse_langs {
+                    (&db_search.lang_left, &db_search.lang_right)
+                } else {
+                    (&db_search.lang_right, &db_search.lang_left)
+                };
+                directions.push(DirectionResults {
+                    language_pair: db_search.language_pair(),
+                    language_from: language_from.to_owned(),
+                    language_to: language_to.to_owned(),
+                    translations,
+                });
+            }
+        }
+    }
+
+    let results = directions.iter().flat_map(|direction| {
+        direction
+            .translations
+            .iter()
+            .map(move |translation| (direction, &translation.source, &translation.target))
+    });
+    let json_result = |(direction, source, target): (&DirectionResults, &String, &String)| {
+        json!({
+            "language_pair": direction.language_pair,
+            "from": direction.language_from,
+            "to": direction.language_to,
+            "source": source,
+            "target": target,
+        })
+    };
+
+    match output_format {
+        OutputFormat::Json => {
+            println!(
+                "{}",
+                json!({
+                    "query": query,
+                    "results": results.map(json_result).collect::<Vec<_>>(),
+                })
+            );
+        }
+        OutputFormat::JsonLines => {
+            for result in results {
+                let mut result = json_result(result);
+                result["query"] = json!(query);
+                println!("{}", result);
+            }
+        }
+        _ => {
+            if directions.is_empty() {
+                return Ok(());
+            }
+            let mut table = new_table(default_table_preset(None, false), None);
+            table.set_header(vec!["DIRECTION", "SOURCE", "TARGET"]);
+            for (direction, source, target) in results {
+                table.add_row(vec![
+                    format!(
+                        "{} → {}",
+                        direction.language_from.to_uppercase(),
+                        direction.language_to.to_uppercase()
+                    ),
+                    source.to_owned(),
+                    target.to_owned(),
+                ]);
+            }
+            println!("{}", table);
+        }
+    }
+
+    Ok(())
+}
+
+/// The translations found in one direction of a language pair, for `search-all`
+struct DirectionResults {
+    language_pair: String,
+    language_from: String,
+    language_to: String,
+    translations: Vec<SearchResult>,
+}
+
+/// Searches the language pair in the given direction, with the defaults of the other options
+fn direction_translations(
+    db_search: &DatabaseSearch,
+    reverse_langs: bool,
+    output_format: OutputFormat,
+) -> SearchTranslations<'_> {
+    let search_translations = listing_translations(db_search, output_format);
+    if !reverse_langs {
+        return search_translations;
+    }
+    let schema = &db_search.schema;
+    SearchTranslations {
+        source_field: &schema.lang_right,
+        target_field: &schema.lang_left,
+        target_frequency_field: &schema.frequency_lang_left,
+        reverse_langs: true,
+        source_lang_upper: db_search.lang_right.to_uppercase(),
+        target_lang_upper: db_search.lang_left.to_uppercase(),
+        ..search_translations
+    }
+}
+
+/// An imported language pair or a path to a database directory
+fn open_database(database: &str) -> Result<DatabaseSearch, DictCliError> {
+    let path = std::path::Path::new(database);
+    if path.is_dir() {
+        DatabaseSearch::open_dir(path)
+    } else {
+        DatabaseSearch::new(&database.to_lowercase())
+    }
+}
+
+fn diff_key(schema: &database::DatabaseSchema, sorted_doc: &SortedDocument) -> (String, String) {
+    let field = |field: &Field| sorted_doc.fields.get(field).copied().unwrap_or_default().to_owned();
+    (field(&schema.lang_left), field(&schema.lang_right))
+}
+
+/// Prints stored documents of the left language as they are, without searching
+fn listing_translations(db_search: &DatabaseSearch, output_format: OutputFormat) -> SearchTranslations<'_> {
+    SearchTranslations {
+        db_search,
+        source_field: &db_search.schema.lang_left,
+        target_field: &db_search.schema.lang_right,
+        target_frequency_field: &db_search.schema.frequency_lang_right,
+        reverse_langs: false,
+        fuzzy_distance: Cell::new(0),
+        max_results_before_tighten: None,
+        max_fuzzy_expansions: None,
+        autocorrect: false,
+        fuzzy_mode: FuzzyMode::PerToken,
+        limit_results: Cell::new(None),
+        skip_results: None,
+        tail_results: None,
+        result_cap: None,
+        search_memory_budget: false,
+        search_extra: true,
+        include_reverse: false,
+        minimum_similarity: None,
+        min_word_length: None,
+        rank: Rank::Similarity,
+        no_sort: false,
+        explain: false,
+        print_query: false,
+        dump: false,
+        output_format,
+        swap_columns: false,
+        column_order: None,
+        align: CellAlignment::Left,
+        show_source: false,
+        target_only: false,
+        show_extra: false,
+        show_phonetic: false,
+        highlight: false,
+        separate_exact: false,
+        footer: false,
+        literal_tokens: false,
+        case_sensitive: false,
+        contains: false,
+        exact_extra: false,
+        table_preset: default_table_preset(None, false),
+        width: None,
+        regex_full: false,
+        search_field: None,
+        back_translate: None,
+        back_translations: RefCell::default(),
+        subject_scope: RefCell::default(),
+        source_lang_upper: db_search.lang_left.to_uppercase(),
+        target_lang_upper: db_search.lang_right.to_uppercase(),
+    }
+}
+
+fn print_language_pairs(json: bool) -> Result<(), DictCliError> {
+    let mut language_pairs = database::available_language_pairs().unwrap_or_default().into_vec();
+    language_pairs.sort_unstable();
+
+    if json {
+        let mut databases = Vec::with_capacity(language_pairs.len());
+        for language_pair in language_pairs.iter() {
+            let (left, right) = database::languages(language_pair)?;
+            let info = database::database_info(language_pair)?;
+            databases.push(json!({
+                "pair": language_pair,
+                "left": left,
+                "right": right,
+                "documents": info.documents,
+                "bytes": info.bytes,
+            }));
+        }
+        println!("{}", serde_json::Value::Array(databases));
+        return Ok(());
+    }
+
+    for language_pair in language_pairs.iter() {
+        let info = database::database_info(language_pair)?;
+        println!(
+            "{} {}",
+            style::bold(language_pair),
+            style::dimmed(&format!("({} entries, {})", info.documents, format_bytes(info.bytes)))
+        );
+    }
+
+    Ok(())
+}
+
+// Invisible characters without width around the matched words of a table cell, so that the columns are measured
+// without the colors, which replace them after rendering
+const EXACT_MATCH_MARKER: &str = "\u{2060}\u{200b}";
+const FUZZY_MATCH_MARKER: &str = "\u{2060}\u{200c}";
+const MATCH_END_MARKER: &str = "\u{2060}\u{200d}";
+
+/// Surrounds the matched byte ranges of the text with the markers of their kind of match
+fn mark_matches(text: &str, spans: Vec<(std::ops::Range<usize>, WordMatch)>) -> String {
+    let mut marked = String::with_capacity(text.len() + spans.len() * 12);
+    let mut end = 0;
+    for (span, word_match) in spans {
+        marked.push_str(&text[end..span.start]);
+        marked.push_str(match word_match {
+            WordMatch::Exact => EXACT_MATCH_MARKER,
+            WordMatch::Fuzzy => FUZZY_MATCH_MARKER,
+        });
+        marked.push_str(&text[span.clone()]);
+        marked.push_str(MATCH_END_MARKER);
+        end = span.end;
+    }
+    marked.push_str(&text[end..]);
+    marked
+}
+
+/// Colors the words between the markers of `mark_matches` in the rendered table, green if exact and yellow if fuzzy.
+/// A word wrapped onto the next line stays uncolored, so that the borders between its parts are not colored.
+fn color_marked_matches(rendered_table: &str) -> String {
+    let marked_word = Regex::new(&format!(
+        "({}|{})(.*?){}",
+        EXACT_MATCH_MARKER, FUZZY_MATCH_MARKER, MATCH_END_MARKER
+    ))
+    .unwrap();
+    let leftover_marker = Regex::new("\u{2060}[\u{200b}\u{200c}\u{200d}]?").unwrap();
+    rendered_table
+        .lines()
+        .map(|line| {
+            let colored = marked_word.replace_all(line, |captures: &regex::Captures| {
+                if &captures[1] == EXACT_MATCH_MARKER {
+                    style::green(&captures[2])
+                } else {
+                    style::yellow(&captures[2])
+                }
+            });
+            leftover_marker.replace_all(&colored, "").into_owned()
+        })
+        .join("\n")
+}
+
+/// Prints the most common word classes and subject labels of the import side by side, see `--summary`
+fn print_label_summary(summary: &ImportSummary, count: usize) {
+    let most_common = |labels: &HashMap<String, usize>| -> Vec<(String, usize)> {
+        labels
+            .iter()
+            .map(|(label, entries)| (label.to_owned(), *entries))
+            .sorted_by(|(label1, entries1), (label2, entries2)| entries2.cmp(entries1).then(label1.cmp(label2)))
+            .take(count)
+            .collect()
+    };
+    let word_classes = most_common(&summary.word_classes);
+    let subject_labels = most_common(&summary.subject_labels);
+
+    let mut table = new_table(default_table_preset(None, false), None);
+    table.set_header(vec!["WORD CLASS", "ENTRIES", "SUBJECT LABEL", "ENTRIES"]);
+    for row in word_classes.into_iter().zip_longest(subject_labels) {
+        let (word_class, subject_label) = row.or_default();
+        let cell = |(label, entries): (String, usize)| {
+            if label.is_empty() {
+                [String::new(), String::new()]
+            } else {
+                [label, entries.to_string()]
+            }
+        };
+        table.add_row(cell(word_class).into_iter().chain(cell(subject_label)));
+    }
+    println!("{}", table);
+}
+
+/// A table with the preset and the width of the options, or of the terminal
+fn new_table(table_preset: &str, width: Option<u16>) -> Table {
+    let mut table = Table::new();
+    table
+        .load_preset(table_preset)
+        .set_content_arrangement(ContentArrangement::Dynamic);
+
+    let columns = std::env::var("COLUMNS").ok();
+    if let Some(width) = table_width(width, table.width(), columns.as_deref()) {
+        table.set_width(width);
+    }
+
+    table
+}
+
+/// The width to set for a table: the given width, otherwise the width of `COLUMNS` if the terminal width is unknown or
+/// zero, and the fallback width if the terminal reports zero. None keeps the terminal width.
+fn table_width(width: Option<u16>, terminal_width: Option<u16>, columns: Option<&str>) -> Option<u16> {
+    if width.is_some() {
+        return width;
+    }
+    if terminal_width.unwrap_or(0) > 0 {
+        return None;
+    }
+    let columns = columns
+        .and_then(|columns| columns.trim().parse().ok())
+        .filter(|columns| *columns > 0);
+    match columns {
+        Some(columns) => Some(columns),
+        None if terminal_width == Some(0) => Some(FALLBACK_TABLE_WIDTH),
+        None => None,
+    }
+}
+
+/// The border style of result tables, if no preset is given.
+/// Plain output, e.g. in logs, only separates the columns.
+fn default_table_preset(table_preset: Option<&'static str>, force_table: bool) -> &'static str {
+    match table_preset {
+        Some(table_preset) => table_preset,
+        None if !force_table && !style::stdout_colored() => ASCII_NO_BORDERS,
+        None => UTF8_FULL,
+    }
+}
+
+pub(crate) fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
 struct SearchTranslations<'a> {
     db_search: &'a DatabaseSearch,
     source_field: &'a Field,
     target_field: &'a Field,
+    target_frequency_field: &'a Field,
     reverse_langs: bool,
-    fuzzy_distance: u8,
-    limit_results: Option<u32>,
-    minimum_similarity: Option<u16>,
-    ascii: bool,
+    /// Can be changed per query in interactive mode
+    fuzzy_distance: Cell<u8>,
+    max_results_before_tighten: Option<u32>,
+    /// Lower the fuzzy distance of words that match more words of the index
+    max_fuzzy_expansions: Option<u32>,
+    /// Replace unknown words by the nearest known word before searching, otherwise it is suggested without results
+    autocorrect: bool,
+    fuzzy_mode: FuzzyMode,
+    /// Can be changed for one query with `:again -r` in interactive mode
+    limit_results: Cell<Option<u32>>,
+    skip_results: Option<u32>,
+    tail_results: Option<u32>,
+    /// Most printed results if no limit is given, see `TERMINAL_RESULT_CAP`
+    result_cap: Option<usize>,
+    search_memory_budget: bool,
+    search_extra: bool,
+    include_reverse: bool,
+    minimum_similarity: Option<f64>,
+    min_word_length: Option<usize>,
+    rank: Rank,
+    /// Keep the order of the index instead of scoring the results, see `--no-sort`
+    no_sort: bool,
+    explain: bool,
+    /// Print the tantivy queries of each search to stderr
+    print_query: bool,
+    dump: bool,
+    output_format: OutputFormat,
+    swap_columns: bool,
+    /// The columns of result tables, see `table_columns`
+    column_order: Option<Vec<TableColumn>>,
+    align: CellAlignment,
+    /// Show the imported file of each result
+    show_source: bool,
+    /// Only the target column, see `--target-only`
+    target_only: bool,
+    show_extra: bool,
+    /// Show the pronunciation of the target
+    show_phonetic: bool,
+    /// Color the words that match the search
+    highlight: bool,
+    /// Show the results with every word of the search first, see `SortedDocument::exact`
+    separate_exact: bool,
+    /// Show the number of results under the table
+    footer: bool,
+    /// Match the whitespace separated parts of the search exactly
+    literal_tokens: bool,
+    case_sensitive: bool,
+    /// Find the words inside of other words
+    contains: bool,
+    /// Match whole annotations instead of the words
+    exact_extra: bool,
+    /// The comfy-table preset of result tables
+    table_preset: &'static str,
+    width: Option<u16>,
+    regex_full: bool,
+    search_field: Option<LabelField>,
+    /// How many results to translate back, if enabled
+    back_translate: Option<u32>,
+    /// Back translations by source and target
+    back_translations: RefCell<HashMap<(String, String), Option<String>>>,
+    /// Only results with this subject label, without brackets and lowercased, set with :subject in interactive mode
+    subject_scope: RefCell<Option<String>>,
     source_lang_upper: String,
     target_lang_upper: String,
 }
 
 impl SearchTranslations<'_> {
-    fn print_results(&self, line: &str) {
-        let results = self
-            .db_search
-            .search_database(self.reverse_langs, line, self.fuzzy_distance);
-
-        match results {
-            Ok(documents) => {
-                let sorted_docs = sort_documents(&documents, self.source_field, line, self.minimum_similarity);
-
-                let mut table = Table::new();
-                let mut has_content = false;
-                table
-                    .load_preset(if self.ascii { ASCII_FULL } else { UTF8_FULL })
-                    .set_content_arrangement(ContentArrangement::Dynamic)
-                    .set_header(vec![&self.source_lang_upper, &self.target_lang_upper]);
-
-                let iter_fn = |field_map: HashMap<Field, &str>| {
-                    table.add_row(vec![field_map[self.source_field], field_map[self.target_field]]);
-                    has_content = true;
+    /// Sorts the results, the results found in the target language come last
+    fn sort_documents<'a>(&self, results: &'a SearchResults, line: &str) -> Vec<SortedDocument<'a>> {
+        let line = &SearchExpression::parse(line).text();
+        let schema = &self.db_search.schema;
+        if self.no_sort {
+            let mut sorted_docs = unsorted_documents(&results.documents);
+            sorted_docs.extend(
+                unsorted_documents(&results.reverse_documents)
+                    .into_iter()
+                    .map(|sorted_doc| SortedDocument {
+                        reverse: true,
+                        ..sorted_doc
+                    }),
+            );
+            return sorted_docs;
+        }
+        let result_filter = ResultFilter {
+            min_similarity: self.minimum_similarity,
+            min_word_length: self.min_word_length,
+        };
+        let mut sorted_docs = sort_documents(
+            &results.documents,
+            self.source_field,
+            schema.field_name(*self.source_field),
+            line,
+            result_filter,
+            self.rank,
+            self.target_frequency_field,
+        );
+        sorted_docs.extend(
+            sort_documents(
+                &results.reverse_documents,
+                self.target_field,
+                schema.field_name(*self.target_field),
+                line,
+                result_filter,
+                self.rank,
+                self.target_frequency_field,
+            )
+            .into_iter()
+            .map(|sorted_doc| SortedDocument {
+                reverse: true,
+                ..sorted_doc
+            }),
+        );
+        if let Rank::WordClasses = self.rank {
+            sort_by_word_classes(&mut sorted_docs, &schema.word_classes);
+        }
+        if self.separate_exact {
+            for sorted_doc in sorted_docs.iter_mut() {
+                let searched_field = if sorted_doc.reverse {
+                    self.target_field
+                } else {
+                    self.source_field
                 };
+                let entry = sorted_doc.fields.get(searched_field).copied().unwrap_or_default();
+                sorted_doc.exact =
+                    self.db_search
+                        .matches_exactly(self.reverse_langs != sorted_doc.reverse, entry, line);
+            }
+            // Stable, so both groups keep their order
+            sorted_docs.sort_by_key(|sorted_doc| !sorted_doc.exact);
+        }
+        sorted_docs
+    }
 
-                if let Some(limit) = &self.limit_results {
-                    sorted_docs.into_iter().take(*limit as usize).for_each(iter_fn);
-                } else {
-                    sorted_docs.into_iter().for_each(iter_fn);
+    /// The code of the target language, e.g. `en`
+    fn target_language(&self) -> &str {
+        self.db_search.schema.field_name(*self.target_field)
+    }
+
+    /// The results in the window of --skip, --limit-results and --tail
+    fn window<T>(&self, items: Vec<T>) -> Vec<T> {
+        let skip = self.skip_results.map_or(0, |skip| skip as usize);
+        let limit = self.limit_results.get().map_or(usize::MAX, |limit| limit as usize);
+        let mut items: Vec<T> = items.into_iter().skip(skip).take(limit).collect();
+        if let Some(tail) = self.tail_results {
+            items.drain(..items.len().saturating_sub(tail as usize));
+        }
+        items
+    }
+
+    /// How many results have to be loaded for the window, all of them for --tail
+    fn window_end(&self) -> Option<usize> {
+        if self.tail_results.is_some() {
+            return None;
+        }
+        let skip = self.skip_results.map_or(0, |skip| skip as usize);
+        self.limit_results.get().map(|limit| skip + limit as usize)
+    }
+
+    /// Whether the first results are the most similar ones, which `--search-memory-budget` collects.
+    /// Otherwise all documents are collected, so that the budget never changes the results.
+    fn keeps_similarity_order(&self) -> bool {
+        !self.no_sort
+            && matches!(self.rank, Rank::Similarity)
+            && self.min_word_length.is_none()
+            && !self.separate_exact
+            && self.max_results_before_tighten.is_none()
+            && self.subject_scope.borrow().is_none()
+    }
+
+    fn search_options(&self) -> SearchOptions {
+        SearchOptions {
+            fuzzy_distance: self.fuzzy_distance.get(),
+            // The footer counts all results, not only those up to the limit
+            max_documents: if self.search_memory_budget && self.keeps_similarity_order() && !self.footer {
+                self.window_end()
+            } else {
+                None
+            },
+            search_extra: self.search_extra,
+            include_reverse: self.include_reverse,
+            literal_tokens: self.literal_tokens,
+            case_sensitive: self.case_sensitive,
+            contains: self.contains,
+            exact_extra: self.exact_extra,
+            print_query: self.print_query,
+            max_fuzzy_expansions: self.max_fuzzy_expansions.map(|max| max as usize),
+            autocorrect: self.autocorrect,
+            fuzzy_mode: self.fuzzy_mode,
+        }
+    }
+
+    /// Searches with the fuzzy distance, or exact if that returns more than `--max-results-before-tighten`.
+    /// The distance that was used in the end is reported whenever the search may be tightened.
+    fn search(&self, line: &str) -> Result<SearchResults, DictCliError> {
+        let options = self.search_options();
+        let results = self.db_search.search_database(self.reverse_langs, line, &options)?;
+
+        if let Some(max_results) = self.max_results_before_tighten {
+            if options.fuzzy_distance > 0 && results.documents.len() > max_results as usize {
+                let exact_options = SearchOptions {
+                    fuzzy_distance: 0,
+                    ..options
+                };
+                let results = self
+                    .db_search
+                    .search_database(self.reverse_langs, line, &exact_options)?;
+                eprintln!(
+                    "Too many results for fuzzy distance {}, used fuzzy distance 0 instead.",
+                    options.fuzzy_distance
+                );
+                return Ok(results);
+            }
+            eprintln!("Used fuzzy distance {}.", options.fuzzy_distance);
+        }
+
+        Ok(results)
+    }
+
+    fn translations(&self, line: &str) -> Result<Vec<SearchResult>, DictCliError> {
+        let results = self.search(line)?;
+        let sorted_docs = self.sort_documents(&results, line);
+
+        Ok(self
+            .window(sorted_docs)
+            .iter()
+            .map(|sorted_doc| self.search_result(line, sorted_doc))
+            .collect())
+    }
+
+    fn search_result(&self, line: &str, sorted_doc: &SortedDocument) -> SearchResult {
+        let field = |field: &Field| sorted_doc.fields.get(field).copied().unwrap_or_default();
+        let source = field(self.source_field);
+        let searched_entry = if sorted_doc.reverse {
+            field(self.target_field)
+        } else {
+            source
+        };
+        let reverse_langs = self.reverse_langs != sorted_doc.reverse;
+        let byte_spans = self
+            .db_search
+            .matched_spans(reverse_langs, searched_entry, line, self.fuzzy_distance.get());
+
+        SearchResult {
+            source: source.to_owned(),
+            target: field(self.target_field).to_owned(),
+            annotations: database::normalized_entry(source, false)
+                .map(|normalized| normalized.annotations)
+                .unwrap_or_default(),
+            similarity: sorted_doc.score.similarity,
+            reverse: sorted_doc.reverse,
+            spans: SearchResult::char_spans(searched_entry, byte_spans),
+        }
+    }
+
+    /// Prints the results and returns them in the printed order
+    fn print_results(&self, line: &str) -> Result<Vec<Document>, DictCliError> {
+        if self.regex_full {
+            return self.print_regex_full(line);
+        }
+
+        if let Some(search_field) = self.search_field {
+            return self.print_label_field(search_field, line);
+        }
+
+        let results = self.search(line)?;
+
+        if results.documents.is_empty() && results.reverse_documents.is_empty() {
+            self.suggest_corrections(line);
+        }
+
+        let mut sorted_docs = self.sort_documents(&results, line);
+        if let Some(subject) = self.subject_scope.borrow().as_deref() {
+            let subject_labels_field = &self.db_search.schema.subject_labels;
+            sorted_docs.retain(|sorted_doc| {
+                has_subject_label(
+                    sorted_doc.fields.get(subject_labels_field).copied().unwrap_or_default(),
+                    subject,
+                )
+            });
+        }
+        let total = sorted_docs.len();
+        let sorted_docs = self.window(sorted_docs);
+        let printed = self.print_sorted_documents(line, &sorted_docs);
+        if self.footer && total > 0 && matches!(self.output_format, OutputFormat::Table) {
+            let count = if printed.len() < total {
+                format!("{} of {} results", printed.len(), total)
+            } else if total == 1 {
+                "1 result".to_owned()
+            } else {
+                format!("{} results", total)
+            };
+            println!("{}", style::dimmed(&format!("— {} —", count)));
+        }
+        Ok(printed)
+    }
+
+    /// Suggests the nearest known word for the words that are in no entry, which `--autocorrect` would search for
+    fn suggest_corrections(&self, line: &str) {
+        if self.autocorrect || self.literal_tokens || self.case_sensitive || self.contains || self.exact_extra {
+            return;
+        }
+        match self.db_search.corrections(self.reverse_langs, line) {
+            Ok(corrections) if !corrections.is_empty() => eprintln!(
+                "Did you mean {}?",
+                corrections
+                    .iter()
+                    .map(|(word, correction)| format!("{} instead of {}", correction, word))
+                    .join(", ")
+            ),
+            Ok(_) => {}
+            Err(err) => eprintln!("Search database error: {}", err),
+        }
+    }
+
+    /// Prints the entries whose complete source text matches the regex, in stored order
+    fn print_regex_full(&self, line: &str) -> Result<Vec<Document>, DictCliError> {
+        // An empty regex matches every entry, a blank search finds nothing like `search_database`
+        if line.trim().is_empty() {
+            return Ok(self.print_sorted_documents(line, &[]));
+        }
+
+        let regex = Regex::new(line)?;
+        let documents = self
+            .db_search
+            .search_regex_full(self.reverse_langs, &regex, self.window_end())?;
+        let documents = self.window(documents);
+        Ok(self.print_sorted_documents(line, &unsorted_documents(&documents)))
+    }
+
+    /// Prints all entries with the searched word classes or subject labels, in stored order
+    fn print_label_field(&self, label_field: LabelField, line: &str) -> Result<Vec<Document>, DictCliError> {
+        let documents = self.db_search.search_label_field(label_field, line)?;
+        let documents = self.window(documents);
+        Ok(self.print_sorted_documents(line, &unsorted_documents(&documents)))
+    }
+
+    /// Prints random entries instead of search results
+    fn print_random(&self, count: usize) -> Result<(), DictCliError> {
+        let documents = self.db_search.random_documents(count)?;
+        self.print_sorted_documents("", &unsorted_documents(&documents));
+        Ok(())
+    }
+
+    /// Prints the stored fields in schema order, one per line, and a blank line after the document
+    fn dump_document(&self, document: &Document) {
+        let schema = &self.db_search.schema;
+        for field_value in document
+            .field_values()
+            .iter()
+            .sorted_by_key(|field_value| field_value.field())
+        {
+            let value = match field_value.value() {
+                Value::Str(text) => format!("{:?}", text),
+                Value::U64(number) => number.to_string(),
+                value => format!("{:?}", value),
+            };
+            eprintln!("{}: {}", schema.field_name(field_value.field()), value);
+        }
+        eprintln!();
+    }
+
+    fn print_sorted_documents(&self, line: &str, sorted_docs: &[SortedDocument]) -> Vec<Document> {
+        let sorted_docs = match self.output_format {
+            OutputFormat::First => &sorted_docs[..sorted_docs.len().min(1)],
+            _ => sorted_docs,
+        };
+        // The JSON formats are read by programs, which need every result
+        let result_cap = self.result_cap.filter(|_| {
+            self.limit_results.get().is_none()
+                && matches!(self.output_format, OutputFormat::Table | OutputFormat::Template(_))
+        });
+        let sorted_docs = match result_cap {
+            Some(result_cap) if sorted_docs.len() > result_cap => {
+                eprintln!(
+                    "Showing the first {} of {} results, use --limit-results to change.",
+                    result_cap,
+                    sorted_docs.len()
+                );
+                &sorted_docs[..result_cap]
+            }
+            _ => sorted_docs,
+        };
+
+        if self.explain {
+            for sorted_doc in sorted_docs.iter() {
+                eprintln!("{}: {}", sorted_doc.fields[self.source_field], sorted_doc.score);
+            }
+        }
+
+        if self.dump {
+            for sorted_doc in sorted_docs.iter() {
+                self.dump_document(sorted_doc.document);
+            }
+        }
+
+        match &self.output_format {
+            OutputFormat::Table if self.separate_exact => {
+                let exact_len = sorted_docs.iter().take_while(|sorted_doc| sorted_doc.exact).count();
+                let (exact_docs, similar_docs) = sorted_docs.split_at(exact_len);
+                for (heading, section_docs) in [("Exact", exact_docs), ("Similar", similar_docs)] {
+                    if !section_docs.is_empty() {
+                        println!("{}", style::bold(heading));
+                        self.print_table(line, section_docs);
+                    }
                 }
+            }
+            OutputFormat::Table => self.print_table(line, sorted_docs),
+            OutputFormat::Json => self.print_json(line, sorted_docs),
+            OutputFormat::JsonLines => self.print_json_lines(line, sorted_docs),
+            OutputFormat::Template(template) => self.print_template(template, sorted_docs),
+            OutputFormat::Anki => self.print_anki(sorted_docs),
+            OutputFormat::First => {
+                for sorted_doc in sorted_docs {
+                    println!("{}", sorted_doc.fields[self.target_field]);
+                }
+            }
+        }
 
-                if has_content {
-                    println!("{}", table);
+        sorted_docs
+            .iter()
+            .map(|sorted_doc| sorted_doc.document.clone())
+            .collect()
+    }
+
+    /// Orders the source and target column for display
+    fn display_order<T>(&self, mut columns: Vec<T>) -> Vec<T> {
+        if self.swap_columns {
+            columns.reverse();
+        }
+        columns
+    }
+
+    /// The columns of `--column-order`, otherwise the source and target with the enabled columns after them
+    fn table_columns(&self) -> Vec<TableColumn> {
+        let mut columns = match &self.column_order {
+            Some(column_order) => column_order.clone(),
+            None => {
+                let mut columns = self.display_order(vec![TableColumn::Source, TableColumn::Target]);
+                if self.show_extra {
+                    columns.push(TableColumn::Extra);
+                }
+                if self.show_phonetic {
+                    columns.push(TableColumn::Phonetic);
+                }
+                if self.show_source {
+                    columns.push(TableColumn::SourceFile);
                 }
+                columns
             }
+        };
+        if self.back_translate.is_some() {
+            let position = if self.column_order.is_some() { columns.len() } else { 2 };
+            columns.insert(position, TableColumn::BackTranslation);
+        }
+        columns
+    }
+
+    fn align_columns(&self, table: &mut Table) {
+        for column in table.column_iter_mut() {
+            column.set_cell_alignment(self.align);
+        }
+    }
+
+    /// Adds the column that marks results found in the target language, if enabled
+    fn marker_column(&self, mut columns: Vec<String>, marker: &str) -> Vec<String> {
+        if self.include_reverse {
+            columns.push(marker.to_owned());
+        }
+        columns
+    }
+
+    fn print_table(&self, line: &str, sorted_docs: &[SortedDocument]) {
+        if sorted_docs.is_empty() {
+            return;
+        }
+
+        if self.target_only {
+            self.print_target_column(sorted_docs);
+            return;
+        }
+
+        let columns = self.table_columns();
+        let mut header: Vec<String> = columns
+            .iter()
+            .map(|column| match column {
+                TableColumn::Source => self.source_lang_upper.clone(),
+                TableColumn::Target => self.target_lang_upper.clone(),
+                TableColumn::WordClasses => String::from("WORD CLASSES"),
+                TableColumn::SubjectLabels => String::from("SUBJECTS"),
+                TableColumn::Extra => String::from("ANNOTATIONS"),
+                TableColumn::Phonetic => String::from("PHONETIC"),
+                TableColumn::SourceFile => String::from("SOURCE FILE"),
+                TableColumn::BackTranslation => format!("{} (BACK)", self.source_lang_upper),
+            })
+            .collect();
+
+        let has_diff_status = sorted_docs.iter().any(|sorted_doc| sorted_doc.diff_status.is_some());
+        if has_diff_status {
+            header.insert(0, String::new());
+        }
+
+        let mut table = self.new_table();
+        table.set_header(self.marker_column(header, ""));
+
+        let highlight = self.highlight && style::stdout_colored();
+        let schema = &self.db_search.schema;
+        for (index, sorted_doc) in sorted_docs.iter().enumerate() {
+            let searched_field = if sorted_doc.reverse {
+                self.target_field
+            } else {
+                self.source_field
+            };
+            let field = |field: &Field| {
+                let text = sorted_doc.fields.get(field).copied().unwrap_or_default();
+                if highlight && field == searched_field {
+                    let reverse_langs = self.reverse_langs != sorted_doc.reverse;
+                    let spans = self
+                        .db_search
+                        .matched_spans(reverse_langs, text, line, self.fuzzy_distance.get());
+                    mark_matches(text, spans)
+                } else {
+                    text.to_owned()
+                }
+            };
+            let mut row: Vec<String> = columns
+                .iter()
+                .map(|column| match column {
+                    TableColumn::Source => field(self.source_field),
+                    TableColumn::Target => field(self.target_field),
+                    TableColumn::WordClasses => field(&schema.word_classes),
+                    TableColumn::SubjectLabels => field(&schema.subject_labels),
+                    TableColumn::Extra => self.extra(sorted_doc),
+                    TableColumn::Phonetic => self.phonetic(sorted_doc),
+                    TableColumn::SourceFile => self.source_file(sorted_doc).to_owned(),
+                    TableColumn::BackTranslation => self.back_translation(index, sorted_doc).unwrap_or_default(),
+                })
+                .collect();
+            if has_diff_status {
+                row.insert(
+                    0,
+                    sorted_doc
+                        .diff_status
+                        .map(DiffStatus::marker)
+                        .unwrap_or_default()
+                        .to_owned(),
+                );
+            }
+            table.add_row(self.marker_column(row, if sorted_doc.reverse { "←" } else { "" }));
+        }
+        self.align_columns(&mut table);
+
+        if highlight {
+            println!("{}", color_marked_matches(&table.to_string()));
+        } else {
+            println!("{}", table);
+        }
+    }
+
+    /// Prints the distinct targets in a single column, in the order of the results
+    fn print_target_column(&self, sorted_docs: &[SortedDocument]) {
+        let mut table = self.new_table();
+        table.set_header(vec![self.target_lang_upper.clone()]);
+        for target in sorted_docs
+            .iter()
+            .map(|sorted_doc| sorted_doc.fields[self.target_field])
+            .unique()
+        {
+            table.add_row(vec![target]);
+        }
+        self.align_columns(&mut table);
+        println!("{}", table);
+    }
+
+    fn new_table(&self) -> Table {
+        new_table(self.table_preset, self.width)
+    }
+
+    /// The best translation of the target back into the source language, other than the source word itself.
+    /// Only the first results are translated back, as every result needs another search.
+    fn back_translation(&self, index: usize, sorted_doc: &SortedDocument) -> Option<String> {
+        if index >= self.back_translate? as usize {
+            return None;
+        }
+
+        let source = sorted_doc.fields[self.source_field];
+        let target = sorted_doc.fields[self.target_field];
+        let cache_key = (source.to_owned(), target.to_owned());
+        if let Some(back_translation) = self.back_translations.borrow().get(&cache_key) {
+            return back_translation.clone();
+        }
+
+        let text = database::normalized_entry(target, false).ok()?.text;
+        let options = SearchOptions {
+            fuzzy_distance: 0,
+            max_documents: None,
+            search_extra: false,
+            include_reverse: false,
+            literal_tokens: false,
+            case_sensitive: false,
+            contains: false,
+            exact_extra: false,
+            print_query: false,
+            max_fuzzy_expansions: None,
+            autocorrect: false,
+            fuzzy_mode: FuzzyMode::PerToken,
+        };
+        let back_translation = match self.db_search.search_database(!self.reverse_langs, &text, &options) {
+            Ok(results) => sort_documents(
+                &results.documents,
+                self.target_field,
+                self.db_search.schema.field_name(*self.target_field),
+                &text,
+                ResultFilter::default(),
+                Rank::Similarity,
+                self.target_frequency_field,
+            )
+            .into_iter()
+            .map(|back_doc| back_doc.fields[self.source_field])
+            .find(|back_translation| normalized_text(back_translation) != normalized_text(source))
+            .map(|back_translation| back_translation.to_owned()),
             Err(err) => {
                 eprintln!("Search database error: {}", err);
+                None
             }
+        };
+
+        self.back_translations
+            .borrow_mut()
+            .insert(cache_key, back_translation.clone());
+        back_translation
+    }
+
+    /// The annotations in angle brackets of the searched entry, as indexed.
+    /// They are derived from the entry, as databases imported with --no-extra-index do not store them.
+    fn extra(&self, sorted_doc: &SortedDocument) -> String {
+        let searched_field = if sorted_doc.reverse {
+            self.target_field
+        } else {
+            self.source_field
+        };
+        let entry = sorted_doc.fields.get(searched_field).copied().unwrap_or_default();
+        database::normalized_entry(entry, true)
+            .map(|normalized| normalized.extra)
+            .unwrap_or_default()
+    }
+
+    /// The pronunciation given in the target entry, empty for most entries as dict.cc rarely has them
+    fn phonetic(&self, sorted_doc: &SortedDocument) -> String {
+        let entry = sorted_doc.fields.get(self.target_field).copied().unwrap_or_default();
+        database::normalized_entry(entry, true)
+            .map(|normalized| normalized.phonetic)
+            .unwrap_or_default()
+    }
+
+    /// The imported file of the result, empty if it was not recorded
+    fn source_file<'d>(&self, sorted_doc: &SortedDocument<'d>) -> &'d str {
+        sorted_doc
+            .fields
+            .get(&self.db_search.schema.source_file)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Adds whether the result was found in the target language, its back translation, annotations and source file, if enabled,
+    /// and whether it was added or removed when comparing databases
+    fn json_result(
+        &self,
+        index: usize,
+        sorted_doc: &SortedDocument,
+        mut result: serde_json::Value,
+    ) -> serde_json::Value {
+        if self.include_reverse {
+            result["reverse"] = json!(sorted_doc.reverse);
+        }
+        if self.back_translate.is_some() {
+            result["back_translation"] = json!(self.back_translation(index, sorted_doc));
+        }
+        if self.show_extra {
+            result["extra"] = json!(self.extra(sorted_doc));
+        }
+        if self.show_phonetic {
+            result["phonetic"] = json!(self.phonetic(sorted_doc));
+        }
+        if self.show_source {
+            result["source_file"] = json!(self.source_file(sorted_doc));
+        }
+        if let Some(diff_status) = sorted_doc.diff_status {
+            result["status"] = json!(diff_status.name());
+        }
+        if self.separate_exact {
+            result["exact"] = json!(sorted_doc.exact);
+        }
+        result
+    }
+
+    /// One JSON object with all results per query
+    fn print_json(&self, line: &str, sorted_docs: &[SortedDocument]) {
+        let results = json!({
+            "query": line,
+            "results": sorted_docs
+                .iter()
+                .enumerate()
+                .map(|(index, sorted_doc)| self.json_result(index, sorted_doc, self.search_result(line, sorted_doc).to_json()))
+                .collect::<Vec<_>>(),
+        });
+        println!("{}", results);
+    }
+
+    fn print_template(&self, template: &Template, sorted_docs: &[SortedDocument]) {
+        let schema = &self.db_search.schema;
+
+        for sorted_doc in sorted_docs {
+            let field = |field: &Field| sorted_doc.fields.get(field).copied().unwrap_or_default().to_owned();
+            println!(
+                "{}",
+                template.render(|placeholder| match placeholder {
+                    Placeholder::Source => field(self.source_field),
+                    Placeholder::Target => field(self.target_field),
+                    Placeholder::WordClasses => field(&schema.word_classes),
+                    Placeholder::SubjectLabels => field(&schema.subject_labels),
+                    Placeholder::Score => sorted_doc.score.similarity.to_string(),
+                })
+            );
+        }
+    }
+
+    /// One flashcard per result with the source on the front and the target on the back, the word classes and
+    /// subject labels are added below the target. The fields are HTML, so that the angle brackets of annotations
+    /// like `<sth.>` are not taken for tags by the importer.
+    fn print_anki(&self, sorted_docs: &[SortedDocument]) {
+        let schema = &self.db_search.schema;
+
+        for sorted_doc in sorted_docs {
+            let field = |field: &Field| {
+                let text = sorted_doc
+                    .fields
+                    .get(field)
+                    .copied()
+                    .unwrap_or_default()
+                    .replace(['\t', '\n'], " ");
+                html_escape::encode_text(text.trim()).into_owned()
+            };
+            let labels = [field(&schema.word_classes), field(&schema.subject_labels)]
+                .into_iter()
+                .filter(|label| !label.is_empty())
+                .join(" ");
+            let mut back = field(self.target_field);
+            if !labels.is_empty() {
+                back.push_str("<br><small>");
+                back.push_str(&labels);
+                back.push_str("</small>");
+            }
+            println!("{}\t{}", field(self.source_field), back);
+        }
+    }
+
+    /// One JSON object per result
+    fn print_json_lines(&self, line: &str, sorted_docs: &[SortedDocument]) {
+        for (index, sorted_doc) in sorted_docs.iter().enumerate() {
+            let mut result = self.search_result(line, sorted_doc).to_json();
+            result["query"] = json!(line);
+            println!("{}", self.json_result(index, sorted_doc, result));
+        }
+    }
+}
+
+#[derive(Default)]
+struct Score {
+    /// Similarity to the entry without brackets
+    text: f64,
+    /// Similarity to the angle bracket annotations
+    extra: f64,
+    /// The better of both similarities, scaled to 0 to 1000
+    similarity: u16,
+}
+
+impl Display for Score {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "text {:.3}, extra {:.3}, used {}, similarity {}",
+            self.text,
+            self.extra,
+            if self.text >= self.extra { "text" } else { "extra" },
+            self.similarity
+        )
+    }
+}
+
+/// Conditions for the results to be shown at all
+#[derive(Clone, Copy, Default)]
+struct ResultFilter {
+    /// From 0 to 1, compared with the similarity before it is scaled
+    min_similarity: Option<f64>,
+    /// In characters of the entry without annotations
+    min_word_length: Option<usize>,
+}
+
+struct SortedDocument<'a> {
+    document: &'a Document,
+    fields: HashMap<Field, &'a str>,
+    score: Score,
+    /// Found by searching the target language
+    reverse: bool,
+    /// Whether the entry was added or removed, when comparing two databases
+    diff_status: Option<DiffStatus>,
+    /// Has every word of the search without fuzzy distance, only determined with `--separate-exact`
+    exact: bool,
+}
+
+#[derive(Clone, Copy)]
+enum DiffStatus {
+    Added,
+    Removed,
+}
+
+impl DiffStatus {
+    fn marker(self) -> &'static str {
+        match self {
+            DiffStatus::Added => "+",
+            DiffStatus::Removed => "-",
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            DiffStatus::Added => "added",
+            DiffStatus::Removed => "removed",
         }
     }
 }
 
+/// The entry without brackets in lowercase
+fn normalized_text(entry: &str) -> String {
+    database::normalized_entry(entry, false)
+        .map(|normalized| normalized.text.to_lowercase())
+        .unwrap_or_default()
+}
+
+/// Documents in their given order, without a score
+fn unsorted_documents(documents: &[Document]) -> Vec<SortedDocument<'_>> {
+    documents
+        .iter()
+        .map(|document| SortedDocument {
+            document,
+            fields: field_map(document),
+            score: Score::default(),
+            reverse: false,
+            diff_status: None,
+            exact: false,
+        })
+        .collect()
+}
+
+/// The text fields of the document
+fn field_map(document: &Document) -> HashMap<Field, &str> {
+    let mut field_map: HashMap<Field, &str> = HashMap::new();
+
+    for field in document.field_values() {
+        if let Some(text) = field.value().as_text() {
+            field_map.insert(field.field(), text);
+        }
+    }
+
+    field_map
+}
+
+/// Scores the entries of the source field by their similarity to the input, lowercased with the rules of its language
 fn sort_documents<'a>(
     documents: &'a [Document],
     source_field: &Field,
+    source_language: &str,
     actual_input: &str,
-    min_similarity: Option<u16>,
-) -> Vec<HashMap<Field, &'a str>> {
-    let actual_input: String = actual_input.to_lowercase().nfc().collect();
+    result_filter: ResultFilter,
+    rank: Rank,
+    target_frequency_field: &Field,
+) -> Vec<SortedDocument<'a>> {
+    let actual_input = database::similarity_input(source_language, actual_input);
 
-    let mut docs_with_fields: Vec<(HashMap<Field, &str>, u16)> = documents
+    let mut docs_with_fields: Vec<(SortedDocument, u64)> = documents
         .iter()
         .filter_map(|document| {
-            let mut field_map: HashMap<Field, &str> = HashMap::new();
+            let field_map = field_map(document);
+            let original_field = field_map.get(source_field).unwrap();
+            let norm_result = database::normalized_entry(original_field, false);
 
-            for field in document.field_values() {
-                if let Some(text) = field.value().as_text() {
-                    field_map.insert(field.field(), text);
+            if let Some(min_word_length) = result_filter.min_word_length {
+                let text = norm_result
+                    .as_ref()
+                    .map_or(*original_field, |normalized| &normalized.text);
+                if text.chars().count() < min_word_length {
+                    return None;
                 }
             }
 
-            let original_field = field_map.get(source_field).unwrap();
-            let norm_result = database::normalized_entry(original_field, false);
-
-            let similarity = (match norm_result {
-                Ok(normalized) => strsim::sorensen_dice(
-                    &normalized.text.to_lowercase().replace('(', "").replace(')', ""),
-                    &actual_input,
-                )
-                .max(strsim::sorensen_dice(&normalized.extra.to_lowercase(), &actual_input)),
-                Err(_) => 0.0,
-            } * 1000.0) as u16;
+            let (text_score, extra_score) = match norm_result {
+                Ok(normalized) => database::similarity(source_language, &normalized, &actual_input),
+                Err(_) => (0.0, 0.0),
+            };
+            let similarity = database::scaled_similarity((text_score, extra_score));
 
-            if let Some(min_similarity) = min_similarity {
-                if similarity < min_similarity {
+            if let Some(min_similarity) = result_filter.min_similarity {
+                if text_score.max(extra_score) < min_similarity {
                     return None;
                 }
             }
 
-            Some((field_map, similarity))
+            let frequency = match rank {
+                Rank::Similarity | Rank::WordClasses => 0,
+                Rank::Frequency => document
+                    .get_first(*target_frequency_field)
+                    .and_then(|value| value.as_u64())
+                    .unwrap_or(0),
+            };
+
+            let sorted_doc = SortedDocument {
+                document,
+                fields: field_map,
+                score: Score {
+                    text: text_score,
+                    extra: extra_score,
+                    similarity,
+                },
+                reverse: false,
+                diff_status: None,
+                exact: false,
+            };
+
+            Some((sorted_doc, frequency))
         })
         .collect();
 
-    docs_with_fields.sort_unstable_by_key(|&(_, similarity)| Reverse(similarity));
-    docs_with_fields.into_iter().map(|(fields, _)| fields).collect()
+    docs_with_fields.sort_by_key(|(sorted_doc, frequency)| Reverse((*frequency, sorted_doc.score.similarity)));
+    docs_with_fields.into_iter().map(|(sorted_doc, _)| sorted_doc).collect()
+}
+
+/// Whether one of the subject labels like `[med.] [pharm.]` is the label, which is lowercased and without brackets
+fn has_subject_label(subject_labels: &str, label: &str) -> bool {
+    database::split_subject_labels(subject_labels).any(|subject_label| subject_label.to_lowercase() == label)
+}
+
+/// Groups the results by their word classes, the results without come last.
+/// The sort is stable, so the results found in the target language stay last and each group stays sorted by similarity.
+fn sort_by_word_classes(sorted_docs: &mut [SortedDocument], word_classes_field: &Field) {
+    sorted_docs.sort_by_key(|sorted_doc| {
+        let word_classes = sorted_doc.fields.get(word_classes_field).copied().unwrap_or_default();
+        (sorted_doc.reverse, word_classes.is_empty(), word_classes)
+    });
+}
+
+/// Completions that continue the line exactly come before those of a word with a typo, each with the fewest words,
+/// then the shortest and then alphabetically first
+fn ranked_completions(line: &str, completions: HashSet<String>) -> Vec<String> {
+    let prefix: String = line.trim().nfc().collect();
+    completions
+        .into_iter()
+        .sorted_unstable_by(|completion1, completion2| {
+            (!completion1.starts_with(&prefix))
+                .cmp(&!completion2.starts_with(&prefix))
+                .then_with(|| {
+                    completion1
+                        .split_whitespace()
+                        .count()
+                        .cmp(&completion2.split_whitespace().count())
+                })
+                .then_with(|| completion1.chars().count().cmp(&completion2.chars().count()))
+                .then_with(|| completion1.cmp(completion2))
+        })
+        .collect()
 }
 
 struct TabCompletion<'a> {
     db_search: &'a DatabaseSearch,
     reverse_langs: bool,
+    /// The completions of the last completed line with the generation of the database they were found in, as
+    /// repeated tab presses on large databases would scan again
+    last_completions: RefCell<Option<(u64, String, Vec<String>)>>,
 }
 impl Helper for TabCompletion<'_> {}
 impl Validator for TabCompletion<'_> {}
@@ -228,16 +2101,17 @@ impl Completer for TabCompletion<'_> {
         _pos: usize,
         _ctx: &rustyline::Context<'_>,
     ) -> rustyline::Result<(usize, Vec<Self::Candidate>)> {
+        let generation = self.db_search.generation();
+        if let Some((last_generation, last_line, completions)) = &*self.last_completions.borrow() {
+            if *last_generation == generation && last_line == line {
+                return Ok((0, completions.clone()));
+            }
+        }
+
         match self.db_search.tab_completions(line, self.reverse_langs) {
             Ok(completions) => {
-                let mut completions: Vec<String> = completions.into_iter().collect();
-                completions.sort_unstable_by(|completion1, completion2| {
-                    completion1
-                        .split_whitespace()
-                        .count()
-                        .cmp(&completion2.split_whitespace().count())
-                        .then_with(|| completion1.chars().count().cmp(&completion2.chars().count()))
-                });
+                let completions = ranked_completions(line, completions);
+                *self.last_completions.borrow_mut() = Some((generation, line.to_owned(), completions.clone()));
                 Ok((0, completions))
             }
             Err(err) => {
@@ -247,3 +2121,66 @@ impl Completer for TabCompletion<'_> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use database::ImportOptions;
+
+    use super::*;
+
+    #[test]
+    fn exact_prefix_completion_comes_first() {
+        let records = b"mouse\tMaus\nhouse\tHaus\nhouses\tH\xc3\xa4user\n";
+        let db_search = DatabaseSearch::in_memory("en-de", records, &ImportOptions::default()).unwrap();
+        let completions = db_search.tab_completions("hous", false).unwrap();
+        // mouse is as long as house, but only matches with a typo
+        assert_eq!(ranked_completions("hous", completions), ["house", "houses", "mouse"]);
+    }
+
+    #[test]
+    fn table_width_of_columns() {
+        assert_eq!(table_width(None, None, Some("100")), Some(100));
+        assert_eq!(table_width(None, Some(0), Some(" 100 ")), Some(100));
+        // The terminal and the option come first
+        assert_eq!(table_width(None, Some(120), Some("100")), None);
+        assert_eq!(table_width(Some(60), None, Some("100")), Some(60));
+        // Without a usable COLUMNS a terminal of zero width gets the fallback width
+        assert_eq!(table_width(None, Some(0), Some("0")), Some(FALLBACK_TABLE_WIDTH));
+        assert_eq!(table_width(None, Some(0), Some("wide")), Some(FALLBACK_TABLE_WIDTH));
+        assert_eq!(table_width(None, None, None), None);
+    }
+
+    #[test]
+    fn table_respects_columns() {
+        let mut table = Table::new();
+        table
+            .load_preset(UTF8_FULL)
+            .set_content_arrangement(ContentArrangement::Dynamic)
+            .set_width(table_width(None, None, Some("40")).unwrap());
+        table.set_header(vec!["EN", "DE"]);
+        table.add_row(vec![
+            "to learn something by heart",
+            "etw. auswendig lernen und nie wieder vergessen",
+        ]);
+        for line in table.to_string().lines() {
+            assert!(line.chars().count() <= 40, "{}", line);
+        }
+    }
+
+    #[test]
+    fn highlight_markers_take_no_width() {
+        let source = "to go online";
+        let marked = mark_matches(source, vec![(0..2, WordMatch::Fuzzy), (6..12, WordMatch::Exact)]);
+        assert_eq!(
+            marked,
+            "\u{2060}\u{200c}to\u{2060}\u{200d} go \u{2060}\u{200b}online\u{2060}\u{200d}"
+        );
+        // Only the marked cell has matches, the same word in the other column is left alone
+        let render = |source: &str| {
+            let mut table = new_table(UTF8_FULL, Some(20));
+            table.add_row(vec![source, "online gehen"]);
+            table.to_string()
+        };
+        assert_eq!(color_marked_matches(&render(&marked)), render(source));
+    }
+}
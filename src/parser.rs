@@ -10,5 +10,5 @@ use crate::error::DictCliError;
 struct LangEntryParser;
 
 pub(crate) fn parse_entry(entry: &str) -> Result<Pairs<'_, Rule>, DictCliError> {
-    Ok(LangEntryParser::parse(Rule::expr, entry)?)
+    LangEntryParser::parse(Rule::expr, entry).map_err(|err| Box::new(err).into())
 }
@@ -0,0 +1,93 @@
+use crate::database::MAX_FUZZY_DISTANCE;
+
+/// A search split into its parts, e.g. `learn "by heart" -school`
+pub(crate) struct SearchExpression<'a> {
+    /// Words that have to match, fuzzy if a fuzzy distance is given
+    pub(crate) words: Vec<&'a str>,
+    /// Double quoted parts that have to match exactly and adjacent
+    pub(crate) phrases: Vec<&'a str>,
+    /// Words prefixed with `-`, entries containing them are excluded
+    pub(crate) excluded: Vec<&'a str>,
+    /// The words and phrases in their original order
+    included: Vec<&'a str>,
+}
+
+impl<'a> SearchExpression<'a> {
+    pub(crate) fn parse(expression: &'a str) -> Self {
+        let mut words = Vec::new();
+        let mut phrases = Vec::new();
+        let mut excluded = Vec::new();
+        let mut included = Vec::new();
+
+        // Every second part is inside quotes, an unclosed quote lasts until the end
+        for (index, part) in expression.split('"').enumerate() {
+            if index % 2 == 1 {
+                if !part.trim().is_empty() {
+                    phrases.push(part);
+                    included.push(part);
+                }
+                continue;
+            }
+
+            for word in part.split_whitespace() {
+                match word.strip_prefix('-') {
+                    Some(excluded_word) if !excluded_word.is_empty() => excluded.push(excluded_word),
+                    _ => {
+                        words.push(word);
+                        included.push(word);
+                    }
+                }
+            }
+        }
+
+        Self {
+            words,
+            phrases,
+            excluded,
+            included,
+        }
+    }
+
+    /// The searched text without quotes and excluded words, to compare the results with
+    pub(crate) fn text(&self) -> String {
+        self.included.join(" ")
+    }
+}
+
+/// Splits a trailing fuzzy distance like in `word ~2` off the search, the suffix is not searched for.
+/// A larger distance than `MAX_FUZZY_DISTANCE` is an error.
+pub(crate) fn fuzzy_distance_suffix(line: &str) -> Result<(&str, Option<u8>), String> {
+    if let Some((search, suffix)) = line.trim_end().rsplit_once(char::is_whitespace) {
+        if let Some(distance) = suffix
+            .strip_prefix('~')
+            .filter(|distance| !distance.is_empty() && distance.bytes().all(|byte| byte.is_ascii_digit()))
+        {
+            return match distance.parse() {
+                Ok(distance) if distance <= MAX_FUZZY_DISTANCE => Ok((search.trim_end(), Some(distance))),
+                _ => Err(format!("The fuzzy distance must be at most {}.", MAX_FUZZY_DISTANCE)),
+            };
+        }
+    }
+    Ok((line, None))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fuzzy_distance_suffixes() {
+        assert_eq!(fuzzy_distance_suffix("bnk ~1"), Ok(("bnk", Some(1))));
+        assert_eq!(fuzzy_distance_suffix("river bnk  ~2 "), Ok(("river bnk", Some(2))));
+        assert_eq!(fuzzy_distance_suffix("bnk"), Ok(("bnk", None)));
+        // Only a number after the tilde is a fuzzy distance
+        assert_eq!(fuzzy_distance_suffix("bnk ~x"), Ok(("bnk ~x", None)));
+        assert_eq!(fuzzy_distance_suffix("~1"), Ok(("~1", None)));
+        for line in ["bnk ~3", "bnk ~300"] {
+            assert_eq!(
+                fuzzy_distance_suffix(line),
+                Err(String::from("The fuzzy distance must be at most 2."))
+            );
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use std::ops::Range;
+
+use serde_json::json;
+
+use crate::database::WordMatch;
+
+/// A translation found by a search, as shown by the table, the JSON output and the interactive terminal UI
+pub(crate) struct SearchResult {
+    pub(crate) source: String,
+    pub(crate) target: String,
+    /// The angle bracket annotations of the source
+    pub(crate) annotations: Vec<String>,
+    /// Similarity of the source to the search, scaled to 0 to 1000
+    pub(crate) similarity: u16,
+    /// Found by searching the target language
+    pub(crate) reverse: bool,
+    /// Character ranges of the words that match a word of the search, in the target if the result was found by
+    /// searching the target language
+    pub(crate) spans: Vec<MatchSpan>,
+}
+
+pub(crate) struct MatchSpan {
+    /// In characters, not bytes
+    pub(crate) range: Range<usize>,
+    pub(crate) word_match: WordMatch,
+}
+
+impl SearchResult {
+    /// The character ranges of the byte ranges within the text
+    pub(crate) fn char_spans(text: &str, byte_spans: Vec<(Range<usize>, WordMatch)>) -> Vec<MatchSpan> {
+        let char_offset = |byte_offset: usize| text[..byte_offset].chars().count();
+        byte_spans
+            .into_iter()
+            .map(|(range, word_match)| MatchSpan {
+                range: char_offset(range.start)..char_offset(range.end),
+                word_match,
+            })
+            .collect()
+    }
+
+    /// The fields that the JSON output has for every result
+    pub(crate) fn to_json(&self) -> serde_json::Value {
+        json!({
+            "source": self.source,
+            "target": self.target,
+            "annotations": self.annotations,
+            "similarity": self.similarity,
+            "matches": self
+                .spans
+                .iter()
+                .map(|span| json!({
+                    "start": span.range.start,
+                    "end": span.range.end,
+                    "exact": span.word_match == WordMatch::Exact,
+                }))
+                .collect::<Vec<_>>(),
+        })
+    }
+}
@@ -0,0 +1,60 @@
+use std::io::ErrorKind;
+use std::process::{Command, Stdio};
+
+use crate::database;
+use crate::error::DictCliError;
+
+/// The text-to-speech programs of the system, in the order they are tried
+#[cfg(target_os = "macos")]
+fn commands(text: &str, _language: &str) -> Vec<Command> {
+    let mut say = Command::new("say");
+    say.arg(text);
+    vec![say]
+}
+
+/// The text-to-speech programs of the system, in the order they are tried
+#[cfg(windows)]
+fn commands(text: &str, _language: &str) -> Vec<Command> {
+    // The text is passed in the environment, so that it needs no quoting in the script
+    let mut powershell = Command::new("powershell");
+    powershell
+        .args([
+            "-NoProfile",
+            "-NonInteractive",
+            "-Command",
+            "Add-Type -AssemblyName System.Speech; \
+            (New-Object System.Speech.Synthesis.SpeechSynthesizer).Speak($env:DICTCC_SPEAK_TEXT)",
+        ])
+        .env("DICTCC_SPEAK_TEXT", text);
+    vec![powershell]
+}
+
+/// The text-to-speech programs of the system, in the order they are tried
+#[cfg(not(any(target_os = "macos", windows)))]
+fn commands(text: &str, language: &str) -> Vec<Command> {
+    let mut spd_say = Command::new("spd-say");
+    spd_say.args(["--wait", "--language", language, "--", text]);
+    let mut espeak_ng = Command::new("espeak-ng");
+    espeak_ng.args(["-v", language, "--", text]);
+    let mut espeak = Command::new("espeak");
+    espeak.args(["-v", language, "--", text]);
+    vec![spd_say, espeak_ng, espeak]
+}
+
+/// Reads the entry aloud in the language, without the annotations in brackets like `{f}`.
+/// The first text-to-speech program that is installed is used.
+pub(crate) fn speak(entry: &str, language: &str) -> Result<(), DictCliError> {
+    let text = database::normalized_entry(entry, false).map_or_else(|_| entry.to_owned(), |normalized| normalized.text);
+
+    for mut command in commands(&text, language) {
+        let program = command.get_program().to_string_lossy().into_owned();
+        match command.stdout(Stdio::null()).status() {
+            Ok(status) if status.success() => return Ok(()),
+            Ok(_) => return Err(DictCliError::SpeechFailed(program)),
+            Err(err) if err.kind() == ErrorKind::NotFound => continue,
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    Err(DictCliError::NoSpeechProgram)
+}
@@ -0,0 +1,35 @@
+use std::path::PathBuf;
+
+use crate::database;
+use crate::error::DictCliError;
+
+const LAST_SELECTION_FILE: &str = "last_selection";
+
+/// The language pair and source language of the last successful search
+pub(crate) struct LastSelection {
+    pub(crate) language_pair: String,
+    pub(crate) language_from: String,
+}
+
+fn last_selection_path() -> Result<PathBuf, DictCliError> {
+    Ok(database::data_dir()?.join(LAST_SELECTION_FILE))
+}
+
+pub(crate) fn last_selection() -> Option<LastSelection> {
+    let content = std::fs::read_to_string(last_selection_path().ok()?).ok()?;
+    let mut lines = content.lines();
+    let language_pair = lines.next()?.trim().to_owned();
+    let language_from = lines.next()?.trim().to_owned();
+    Some(LastSelection {
+        language_pair,
+        language_from,
+    })
+}
+
+pub(crate) fn store_last_selection(language_pair: &str, language_from: &str) -> Result<(), DictCliError> {
+    std::fs::write(
+        last_selection_path()?,
+        format!("{}\n{}\n", language_pair, language_from),
+    )?;
+    Ok(())
+}
@@ -0,0 +1,62 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use atty::Stream;
+
+static STDOUT_COLORED: AtomicBool = AtomicBool::new(false);
+static STDERR_COLORED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Copy)]
+pub(crate) enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+pub(crate) fn set_color_choice(color_choice: ColorChoice) {
+    let colored = |stream: Stream| match color_choice {
+        ColorChoice::Auto => std::env::var_os("NO_COLOR").map_or(true, |value| value.is_empty()) && atty::is(stream),
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+    };
+
+    STDOUT_COLORED.store(colored(Stream::Stdout), Ordering::Relaxed);
+    STDERR_COLORED.store(colored(Stream::Stderr), Ordering::Relaxed);
+}
+
+/// Whether stdout is colored, which is false if it is not a terminal or colors are disabled
+pub(crate) fn stdout_colored() -> bool {
+    STDOUT_COLORED.load(Ordering::Relaxed)
+}
+
+fn paint(text: &str, code: &str, colored: &AtomicBool) -> String {
+    if colored.load(Ordering::Relaxed) {
+        format!("\x1b[{}m{}\x1b[0m", code, text)
+    } else {
+        text.to_owned()
+    }
+}
+
+/// Bold text for stdout
+pub(crate) fn bold(text: &str) -> String {
+    paint(text, "1", &STDOUT_COLORED)
+}
+
+/// Dimmed text for stdout
+pub(crate) fn dimmed(text: &str) -> String {
+    paint(text, "2", &STDOUT_COLORED)
+}
+
+/// Green text for stdout
+pub(crate) fn green(text: &str) -> String {
+    paint(text, "32", &STDOUT_COLORED)
+}
+
+/// Yellow text for stdout
+pub(crate) fn yellow(text: &str) -> String {
+    paint(text, "33", &STDOUT_COLORED)
+}
+
+/// Bold text for stderr
+pub(crate) fn bold_err(text: &str) -> String {
+    paint(text, "1", &STDERR_COLORED)
+}
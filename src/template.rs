@@ -0,0 +1,95 @@
+#[derive(Clone, Copy)]
+pub(crate) enum Placeholder {
+    Source,
+    Target,
+    WordClasses,
+    SubjectLabels,
+    Score,
+}
+
+impl Placeholder {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "source" => Some(Self::Source),
+            "target" => Some(Self::Target),
+            "word_classes" => Some(Self::WordClasses),
+            "subject_labels" => Some(Self::SubjectLabels),
+            "score" => Some(Self::Score),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone)]
+enum TemplatePart {
+    Text(String),
+    Placeholder(Placeholder),
+}
+
+/// A line format like `{source} => {target}`, where `{{` and `}}` are literal braces
+#[derive(Clone)]
+pub(crate) struct Template(Vec<TemplatePart>);
+
+impl Template {
+    pub(crate) fn parse(template: &str) -> Result<Self, String> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    let mut closed = false;
+                    for c in chars.by_ref() {
+                        if c == '}' {
+                            closed = true;
+                            break;
+                        }
+                        name.push(c);
+                    }
+                    if !closed {
+                        return Err(String::from("unclosed {, use {{ for a literal brace"));
+                    }
+                    let placeholder = Placeholder::from_name(&name).ok_or_else(|| {
+                        format!(
+                            "unknown placeholder {{{}}}, available are: {{source}}, {{target}}, {{word_classes}}, \
+                            {{subject_labels}}, {{score}}",
+                            name
+                        )
+                    })?;
+                    if !text.is_empty() {
+                        parts.push(TemplatePart::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(TemplatePart::Placeholder(placeholder));
+                }
+                '}' => return Err(String::from("unmatched }, use }} for a literal brace")),
+                _ => text.push(c),
+            }
+        }
+
+        if !text.is_empty() {
+            parts.push(TemplatePart::Text(text));
+        }
+
+        Ok(Self(parts))
+    }
+
+    pub(crate) fn render<F: Fn(Placeholder) -> String>(&self, value: F) -> String {
+        self.0
+            .iter()
+            .map(|part| match part {
+                TemplatePart::Text(text) => text.clone(),
+                TemplatePart::Placeholder(placeholder) => value(*placeholder),
+            })
+            .collect()
+    }
+}
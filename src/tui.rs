@@ -0,0 +1,262 @@
+use std::io::{stdout, Stdout, Write};
+use std::time::{Duration, Instant};
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{cursor, execute, queue};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::error::DictCliError;
+use crate::result::{MatchSpan, SearchResult};
+use crate::SearchTranslations;
+
+/// Time without input before the results are updated
+const DEBOUNCE: Duration = Duration::from_millis(150);
+/// Lines above the results: search box, status and header
+const RESULTS_TOP: u16 = 3;
+
+struct TuiState {
+    input: String,
+    input_changed: Option<Instant>,
+    results: Vec<SearchResult>,
+    selected: usize,
+    scroll_offset: usize,
+    status: String,
+}
+
+pub(crate) fn run(search_translations: &SearchTranslations) -> Result<(), DictCliError> {
+    let mut stdout = stdout();
+    terminal::enable_raw_mode()?;
+    execute!(stdout, EnterAlternateScreen)?;
+
+    let result = event_loop(&mut stdout, search_translations);
+
+    execute!(stdout, LeaveAlternateScreen)?;
+    terminal::disable_raw_mode()?;
+    result
+}
+
+fn event_loop(stdout: &mut Stdout, search_translations: &SearchTranslations) -> Result<(), DictCliError> {
+    let mut state = TuiState {
+        input: String::new(),
+        input_changed: None,
+        results: Vec::new(),
+        selected: 0,
+        scroll_offset: 0,
+        status: String::from("Type to search, Enter to copy, Esc to quit"),
+    };
+
+    let mut redraw = true;
+
+    loop {
+        if let Some(input_changed) = state.input_changed {
+            if input_changed.elapsed() >= DEBOUNCE {
+                redraw = true;
+                state.input_changed = None;
+                state.selected = 0;
+                state.scroll_offset = 0;
+                match search_translations.translations(&state.input) {
+                    Ok(results) => {
+                        state.status = format!("{} results", results.len());
+                        state.results = results;
+                    }
+                    Err(err) => {
+                        state.status = format!("Search database error: {}", err);
+                        state.results.clear();
+                    }
+                }
+            }
+        }
+
+        if redraw {
+            draw(stdout, search_translations, &mut state)?;
+            redraw = false;
+        }
+
+        if !event::poll(DEBOUNCE)? {
+            continue;
+        }
+
+        let key = match event::read()? {
+            Event::Key(key) if key.kind != KeyEventKind::Release => key,
+            Event::Resize(_, _) => {
+                redraw = true;
+                continue;
+            }
+            _ => continue,
+        };
+        redraw = true;
+
+        match key {
+            KeyEvent { code: KeyCode::Esc, .. } => break,
+            KeyEvent {
+                code: KeyCode::Char('c'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            } => break,
+            KeyEvent { code: KeyCode::Up, .. } => {
+                state.selected = state.selected.saturating_sub(1);
+            }
+            KeyEvent {
+                code: KeyCode::Down, ..
+            } if state.selected + 1 < state.results.len() => {
+                state.selected += 1;
+            }
+            KeyEvent {
+                code: KeyCode::Enter, ..
+            } => {
+                if let Some(result) = state.results.get(state.selected) {
+                    copy_to_clipboard(stdout, &result.target)?;
+                    state.status = format!("Copied: {}", result.target);
+                }
+            }
+            KeyEvent {
+                code: KeyCode::Backspace,
+                ..
+            } => {
+                state.input.pop();
+                state.input_changed = Some(Instant::now());
+            }
+            KeyEvent {
+                code: KeyCode::Char(c),
+                modifiers,
+                ..
+            } if !modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) => {
+                state.input.push(c);
+                state.input_changed = Some(Instant::now());
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+fn draw(
+    stdout: &mut Stdout,
+    search_translations: &SearchTranslations,
+    state: &mut TuiState,
+) -> Result<(), DictCliError> {
+    let (columns, rows) = terminal::size()?;
+    let columns = columns as usize;
+    let visible_results = rows.saturating_sub(RESULTS_TOP) as usize;
+
+    if state.selected < state.scroll_offset {
+        state.scroll_offset = state.selected;
+    } else if visible_results > 0 && state.selected >= state.scroll_offset + visible_results {
+        state.scroll_offset = state.selected + 1 - visible_results;
+    }
+
+    let source_width = state
+        .results
+        .iter()
+        .map(|result| result.source.width())
+        .chain(std::iter::once(search_translations.source_lang_upper.width()))
+        .max()
+        .unwrap_or(0)
+        .min(columns / 2);
+
+    queue!(
+        stdout,
+        Clear(ClearType::All),
+        cursor::MoveTo(0, 1),
+        SetAttribute(Attribute::Dim),
+        Print(truncate(&state.status, columns)),
+        SetAttribute(Attribute::Reset),
+        cursor::MoveTo(0, 2),
+        SetAttribute(Attribute::Bold),
+        Print(row(
+            &search_translations.source_lang_upper,
+            &[],
+            &search_translations.target_lang_upper,
+            source_width,
+            columns
+        )),
+        SetAttribute(Attribute::Reset),
+    )?;
+
+    for (line, (index, result)) in state
+        .results
+        .iter()
+        .enumerate()
+        .skip(state.scroll_offset)
+        .take(visible_results)
+        .enumerate()
+    {
+        queue!(stdout, cursor::MoveTo(0, RESULTS_TOP + line as u16))?;
+        if index == state.selected {
+            queue!(stdout, SetAttribute(Attribute::Reverse))?;
+        }
+        queue!(
+            stdout,
+            Print(row(
+                &result.source,
+                if result.reverse { &[] } else { &result.spans },
+                &result.target,
+                source_width,
+                columns
+            )),
+            SetAttribute(Attribute::Reset)
+        )?;
+    }
+
+    let input = truncate(&state.input, columns.saturating_sub(2));
+    queue!(
+        stdout,
+        cursor::MoveTo(0, 0),
+        Print("> "),
+        Print(&input),
+        cursor::MoveTo(2 + input.width() as u16, 0)
+    )?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// The matched words of the source are bold
+fn row(source: &str, spans: &[MatchSpan], target: &str, source_width: usize, columns: usize) -> String {
+    let source = truncate(source, source_width);
+    let padding = " ".repeat(source_width - source.width());
+    let target = truncate(target, columns.saturating_sub(source_width + 3));
+    format!("{}{} │ {}", bold_spans(&source, spans), padding, target)
+}
+
+/// Bolds the character ranges of the text, the text has to be truncated before as the escape sequences have a width
+fn bold_spans(text: &str, spans: &[MatchSpan]) -> String {
+    let mut bolded = String::new();
+    let mut bold = false;
+    for (index, c) in text.chars().enumerate() {
+        let in_span = spans.iter().any(|span| span.range.contains(&index));
+        if in_span != bold {
+            let attribute = if in_span {
+                Attribute::Bold
+            } else {
+                Attribute::NormalIntensity
+            };
+            bolded.push_str(&format!("\x1b[{}m", attribute.sgr()));
+            bold = in_span;
+        }
+        bolded.push(c);
+    }
+    if bold {
+        bolded.push_str(&format!("\x1b[{}m", Attribute::NormalIntensity.sgr()));
+    }
+    bolded
+}
+
+fn truncate(text: &str, max_width: usize) -> String {
+    let mut width = 0;
+    text.chars()
+        .take_while(|c| {
+            width += c.width().unwrap_or(0);
+            width <= max_width
+        })
+        .collect()
+}
+
+/// Copies the text to the clipboard with the OSC 52 escape sequence
+fn copy_to_clipboard(stdout: &mut Stdout, text: &str) -> Result<(), DictCliError> {
+    write!(stdout, "\x1b]52;c;{}\x07", base64::encode(text))?;
+    stdout.flush()?;
+    Ok(())
+}